@@ -0,0 +1,18 @@
+//! Assembles the effect program the firmware plays as its last mode:
+//! `host/programs/wave.tla`, or the one `TRAILER_LIGHT_PROGRAM` points to.
+
+use std::{env, fs, path::PathBuf};
+
+fn main() {
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let program = match env::var("TRAILER_LIGHT_PROGRAM") {
+        Ok(path) => PathBuf::from(path),
+        Err(_) => manifest_dir.join("../host/programs/wave.tla"),
+    };
+    println!("cargo:rerun-if-env-changed=TRAILER_LIGHT_PROGRAM");
+    println!("cargo:rerun-if-changed={}", program.display());
+
+    let code = trailer_light_host::asm::load(&program).unwrap_or_else(|e| panic!("{}", e));
+    let out = PathBuf::from(env::var("OUT_DIR").unwrap()).join("program.bin");
+    fs::write(out, code).unwrap();
+}
@@ -0,0 +1,217 @@
+#![no_std]
+#![no_main]
+
+use core::cell::RefCell;
+
+use bare_metal::Mutex;
+use esp32c3_hal::{
+    clock::ClockControl,
+    gpio::Gpio3,
+    gpio_types::{Event, Input, Pin, PullUp},
+    i2c::I2C,
+    interrupt, pac,
+    prelude::*,
+    pulse_control::ClockSource,
+    systimer::SystemTimer,
+    timer::TimerGroup,
+    utils::SmartLedsAdapter,
+    Delay, PulseControl, Rtc, IO,
+};
+#[allow(unused_imports)]
+use panic_halt;
+use riscv_rt::entry;
+use trailer_light_core::{
+    brake::BrakeLight,
+    braking::{Axis, BrakeDetector},
+    button::Button,
+    controls::{Command, Controls, Input as ControlInput, FIRST_MODE, MODES},
+    effect::{Tempo, NORMAL_TEMPO},
+    effects::Scene,
+    gamma,
+    imu::{Accelerometer, Mpu6050},
+    indicator::{Indicator, Indicators},
+    sequencer::{concat, Next, Sequencer, Step, Until},
+    time::{Clock, Instant},
+    transition,
+    wheel::{self, WheelSensor},
+    TrailerLight, NUM_LEDS,
+};
+
+/// Effect program assembled by the build script, see `build.rs`.
+static PROGRAM: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/program.bin"));
+
+/// The boot sequence and the modes of the button, with the program as the
+/// last mode.
+static PLAYLIST: [Step<ControlInput>; MODES.len() + 1] = {
+    let mut steps = concat(
+        MODES,
+        [Step::new(
+            Scene::Program(PROGRAM),
+            Until::Event(ControlInput::NextMode),
+        )
+        .fade_in(transition::MODE_CHANGE)
+        .then(Next::Goto(FIRST_MODE))],
+    );
+    // continue with the program after the last mode
+    let last = MODES.len() - 1;
+    steps[last] = MODES[last].then(Next::Continue);
+    steps
+};
+
+/// Reed switch on the wheel, closing to ground once per revolution.
+static WHEEL_PIN: Mutex<RefCell<Option<Gpio3<Input<PullUp>>>>> = Mutex::new(RefCell::new(None));
+static WHEEL: Mutex<RefCell<WheelSensor>> = Mutex::new(RefCell::new(WheelSensor::new()));
+
+/// Time since boot from the free-running system timer.
+struct SysTimerClock;
+
+impl Clock for SysTimerClock {
+    fn now(&self) -> Instant {
+        Instant::from_micros(SystemTimer::now() / (SystemTimer::TICKS_PER_SECOND / 1_000_000))
+    }
+}
+
+#[entry]
+fn main() -> ! {
+    let peripherals = pac::Peripherals::take().unwrap();
+    let mut system = peripherals.SYSTEM.split();
+    let clocks = ClockControl::boot_defaults(system.clock_control).freeze();
+
+    let mut rtc = Rtc::new(peripherals.RTC_CNTL);
+    let timer_group0 = TimerGroup::new(peripherals.TIMG0, &clocks);
+    let mut wdt0 = timer_group0.wdt;
+    let io = IO::new(peripherals.GPIO, peripherals.IO_MUX);
+
+    // Disable watchdogs
+    rtc.swd.disable();
+    rtc.rwdt.disable();
+    wdt0.disable();
+
+    // Delay peripheral
+    let delay = Delay::new(&clocks);
+
+    // Configure RMT peripheral globally
+    let pulse = PulseControl::new(
+        peripherals.RMT,
+        &mut system.peripheral_clock_control,
+        ClockSource::APB,
+        0,
+        0,
+        0,
+    )
+    .unwrap();
+
+    // Initialize the LED driver
+    //   3 colors (RGB) * 8 bits per color = 24
+    //   additional + 1 for the end marker
+    let led = SmartLedsAdapter::<_, _, { NUM_LEDS * 24 + 1 }>::new(pulse.channel0, io.pins.gpio8);
+
+    let mut wheel_pin = io.pins.gpio3.into_pull_up_input();
+    wheel_pin.listen(Event::FallingEdge);
+    riscv::interrupt::free(|cs| WHEEL_PIN.borrow(cs).replace(Some(wheel_pin)));
+    interrupt::enable(pac::Interrupt::GPIO, interrupt::Priority::Priority1).unwrap();
+    unsafe {
+        riscv::interrupt::enable();
+    }
+
+    // momentary button to ground
+    let button_pin = io.pins.gpio4.into_pull_up_input();
+    let mut button = Button::new();
+
+    // indicator switch on the handlebar, closing to ground for either side,
+    // both for the hazard lights
+    let left_pin = io.pins.gpio5.into_pull_up_input();
+    let right_pin = io.pins.gpio6.into_pull_up_input();
+
+    // MPU-6050 with its X axis pointing ahead, the light runs on the wheel
+    // sensor alone if there is none
+    let i2c = I2C::new(
+        peripherals.I2C0,
+        io.pins.gpio1,
+        io.pins.gpio2,
+        400u32.kHz(),
+        &mut system.peripheral_clock_control,
+        &clocks,
+    )
+    .unwrap();
+    let mut imu = Mpu6050::new(i2c, Mpu6050::<I2C<pac::I2C0>>::ADDRESS).ok();
+    let mut detector = BrakeDetector::new(Axis::X);
+
+    let mut tl = TrailerLight::new(led, delay, SysTimerClock);
+    // the brightness levels of the effects were picked by eye on linear
+    // output, a curve would dim the steady light to a fraction of it
+    tl.set_gamma(&gamma::LINEAR);
+
+    tl.black();
+
+    let mut sequencer = Sequencer::new(&PLAYLIST);
+    sequencer.start(tl.now());
+    // the modes speed up with the riding speed and follow the button, the
+    // indicators flash on top, braking overrides everything
+    let mut light = BrakeLight::new(Indicators::new(Controls::new(Tempo::new(sequencer))));
+    let mut braking = light.player().player().settings().sensitivity.hysteresis();
+    loop {
+        let now = tl.now();
+        if let Some(gesture) = button.update(button_pin.is_low().unwrap(), now) {
+            let controls = light.player().player();
+            match controls.gesture(gesture) {
+                Some(Command::NextMode) => {
+                    let tempo = controls.player();
+                    let now = tempo.now();
+                    tempo.player().event(ControlInput::NextMode, now);
+                }
+                Some(Command::Power(false)) => light.cancel(),
+                Some(Command::Settings(settings)) => {
+                    // a brake that is applied stays applied until released
+                    let thresholds = settings.sensitivity.hysteresis();
+                    braking.set_thresholds(thresholds);
+                    detector.set_thresholds(thresholds);
+                }
+                _ => {}
+            }
+        }
+
+        // the switches are ignored while the light is off
+        let on = light.player().player().is_on();
+        let left = on && left_pin.is_low().unwrap();
+        let right = on && right_pin.is_low().unwrap();
+        let indicators = light.player();
+        match (left, right) {
+            (true, true) => indicators.start(Indicator::Hazard, now),
+            (true, false) => indicators.start(Indicator::Left, now),
+            (false, true) => indicators.start(Indicator::Right, now),
+            (false, false) => indicators.stop(),
+        }
+
+        let (speed, deceleration) = riscv::interrupt::free(|cs| {
+            let sensor = WHEEL.borrow(cs).borrow();
+            (sensor.speed(now), sensor.deceleration_mg(now))
+        });
+        let controls = light.player().player();
+        let tempo = match controls.settings().follow_speed {
+            true => wheel::tempo(speed),
+            false => NORMAL_TEMPO,
+        };
+        controls.player().set_tempo(tempo);
+        // the filters keep running while the light is off
+        if let Some(accel) = imu.as_mut().and_then(|imu| imu.accel().ok()) {
+            detector.update(accel, now);
+        }
+        if controls.is_on() {
+            braking.update(deceleration);
+            light.brake(braking.is_braking() || detector.is_braking(), now);
+        }
+        tl.step(&mut light);
+    }
+}
+
+#[interrupt]
+fn GPIO() {
+    let now = SysTimerClock.now();
+    riscv::interrupt::free(|cs| {
+        if let Some(pin) = WHEEL_PIN.borrow(cs).borrow_mut().as_mut() {
+            pin.clear_interrupt();
+        }
+        WHEEL.borrow(cs).borrow_mut().pulse(now);
+    });
+}
@@ -0,0 +1,49 @@
+//! Compares the `f32` and the fixed-point implementation of the animation
+//! math. Run with `cargo bench`.
+//!
+//! On the host both run on an FPU, so this mostly shows the fixed-point
+//! version isn't slower. On the ESP32-C3 the difference is much bigger since
+//! every float operation is a soft-float call there.
+
+use std::time::Duration;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use trailer_light_core::{fixed::FixedAnimationContext, AnimationContext, NUM_LEDS};
+
+// parameters of the wave animation
+const PARAMS: (f32, f32, f32, f32, f32, f32, f32) =
+    (-3.0, 32.0, 43.0 / 3.0, 60.0, 60.0, 255.0, 6.0);
+// roughly the frame time on the ESP32-C3
+const FRAME_TIME: Duration = Duration::from_millis(3);
+
+fn wave(c: &mut Criterion) {
+    let (start, end, speed, bb, tb, hb, hw) = PARAMS;
+    let mut group = c.benchmark_group("wave");
+
+    group.bench_function("f32", |b| {
+        let mut v = [0; NUM_LEDS / 2];
+        b.iter(|| {
+            let ctx = AnimationContext::new(start, end, speed, bb, tb, hb, hw);
+            let mut t = Duration::ZERO;
+            while ctx.render(black_box(t), black_box(&mut v)) {
+                t += FRAME_TIME;
+            }
+        })
+    });
+
+    group.bench_function("fixed", |b| {
+        let mut v = [0; NUM_LEDS / 2];
+        b.iter(|| {
+            let ctx = FixedAnimationContext::new(start, end, speed, bb, tb, hb, hw);
+            let mut t = Duration::ZERO;
+            while ctx.render(black_box(t), black_box(&mut v)) {
+                t += FRAME_TIME;
+            }
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, wave);
+criterion_main!(benches);
@@ -0,0 +1,6 @@
+# The wave of the built-in `Wave` effect: a bright highlight moving out from
+# the center on both sides, over the base brightness of 60.
+color 255 0 0
+mirror on
+sweep 29 29 -3 32 14.33 60 60 255 6
+end
@@ -0,0 +1,286 @@
+//! Assembler for the bytecode of [`trailer_light_core::vm`].
+//!
+//! One instruction per line, arguments separated by whitespace, `#` starts a
+//! comment:
+//!
+//! ```text
+//! color <r> <g> <b>
+//! fill <first> <count>
+//! sweep <first> <count> <start> <end> <speed> <bb> <tb> <hb> <hw>
+//! mirror on|off
+//! wait <ms>
+//! loop [<times>]
+//! next
+//! end
+//! ```
+//!
+//! `speed` is in LEDs per second and `hw` in LEDs, both may have decimals.
+//! `loop` without a count repeats forever.
+
+use std::{error::Error, fmt, fs, path::Path, str::FromStr};
+
+use trailer_light_core::{fixed, vm};
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for AsmError {}
+
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    let mut code = Vec::new();
+    let mut open_loops = Vec::new();
+    for (i, line) in source.lines().enumerate() {
+        let err = |message: String| AsmError {
+            line: i + 1,
+            message,
+        };
+        let line = line.split('#').next().unwrap();
+        let mut tokens = line.split_whitespace();
+        let Some(mnemonic) = tokens.next() else {
+            continue;
+        };
+        let args: Vec<&str> = tokens.collect();
+        let arg = |n: usize| args[n];
+        let num = |n: usize| parse::<u8>(arg(n)).map_err(err);
+        let expect_args = |n: usize| {
+            if args.len() == n {
+                Ok(())
+            } else {
+                Err(err(format!(
+                    "`{}` takes {} arguments, found {}",
+                    mnemonic,
+                    n,
+                    args.len()
+                )))
+            }
+        };
+
+        match mnemonic {
+            "end" => {
+                expect_args(0)?;
+                code.push(vm::END);
+            }
+            "color" => {
+                expect_args(3)?;
+                code.extend([vm::COLOR, num(0)?, num(1)?, num(2)?]);
+            }
+            "fill" => {
+                expect_args(2)?;
+                code.extend([vm::FILL, num(0)?, num(1)?]);
+            }
+            "sweep" => {
+                expect_args(9)?;
+                let start = parse::<i8>(arg(2)).map_err(err)?;
+                let end = parse::<i8>(arg(3)).map_err(err)?;
+                for (i, pos) in [(2, start), (3, end)] {
+                    if !fixed::in_range(pos.into()) {
+                        return Err(err(format!(
+                            "position `{}` is too far from the strip",
+                            arg(i)
+                        )));
+                    }
+                }
+                let speed = scaled(arg(4), 100.0, u16::MAX.into()).map_err(err)?;
+                if speed == 0.0 {
+                    return Err(err(format!("speed `{}` doesn't move", arg(4))));
+                }
+                let hw = scaled(arg(8), 10.0, u8::MAX.into()).map_err(err)?;
+                code.extend([vm::SWEEP, num(0)?, num(1)?, start as u8, end as u8]);
+                code.extend((speed as u16).to_le_bytes());
+                code.extend([num(5)?, num(6)?, num(7)?, hw as u8]);
+            }
+            "mirror" => {
+                expect_args(1)?;
+                let on = match arg(0) {
+                    "on" => 1,
+                    "off" => 0,
+                    other => return Err(err(format!("expected `on` or `off`, found `{}`", other))),
+                };
+                code.extend([vm::MIRROR, on]);
+            }
+            "wait" => {
+                expect_args(1)?;
+                let ms = parse::<u16>(arg(0)).map_err(err)?;
+                code.push(vm::WAIT);
+                code.extend(ms.to_le_bytes());
+            }
+            "loop" => {
+                let times = match args.len() {
+                    0 => 0,
+                    _ => {
+                        expect_args(1)?;
+                        match num(0)? {
+                            0 => return Err(err("a loop needs at least one iteration".into())),
+                            n => n,
+                        }
+                    }
+                };
+                open_loops.push(i + 1);
+                if open_loops.len() > vm::MAX_LOOP_DEPTH {
+                    return Err(err(format!(
+                        "loops can't be nested deeper than {}",
+                        vm::MAX_LOOP_DEPTH
+                    )));
+                }
+                code.extend([vm::LOOP, times]);
+            }
+            "next" => {
+                expect_args(0)?;
+                open_loops
+                    .pop()
+                    .ok_or_else(|| err("`next` without `loop`".into()))?;
+                code.push(vm::NEXT);
+            }
+            other => return Err(err(format!("unknown instruction `{}`", other))),
+        }
+    }
+    if let Some(line) = open_loops.pop() {
+        return Err(AsmError {
+            line,
+            message: "`loop` without `next`".into(),
+        });
+    }
+    Ok(code)
+}
+
+/// Reads and assembles the program at `path`.
+pub fn load(path: impl AsRef<Path>) -> Result<Vec<u8>, Box<dyn Error>> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path)?;
+    assemble(&source).map_err(|e| format!("{}: {}", path.display(), e).into())
+}
+
+fn parse<T: FromStr>(s: &str) -> Result<T, String> {
+    s.parse().map_err(|_| format!("invalid number `{}`", s))
+}
+
+/// Parses a decimal number and converts it to units of `1 / scale`.
+fn scaled(s: &str, scale: f64, max: f64) -> Result<f64, String> {
+    let v = parse::<f64>(s)? * scale;
+    if (0.0..=max).contains(&v) {
+        Ok(v.round())
+    } else {
+        Err(format!("`{}` is out of range", s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use trailer_light_core::{effects::Wave, Color16, Effect, NUM_LEDS};
+
+    use super::*;
+
+    #[test]
+    fn encodes_instructions() {
+        let code = assemble(
+            "# comment\n\
+             color 255 0 10\n\
+             \n\
+             loop 3  # three times\n\
+             fill 0 58\n\
+             wait 1000\n\
+             next\n\
+             mirror on\n\
+             sweep 29 29 -3 32 14.33 60 60 255 6\n\
+             end\n",
+        )
+        .unwrap();
+        assert_eq!(
+            code,
+            [
+                vm::COLOR,
+                255,
+                0,
+                10,
+                vm::LOOP,
+                3,
+                vm::FILL,
+                0,
+                58,
+                vm::WAIT,
+                0xe8,
+                0x03,
+                vm::NEXT,
+                vm::MIRROR,
+                1,
+                vm::SWEEP,
+                29,
+                29,
+                0xfd,
+                32,
+                0x99,
+                0x05,
+                60,
+                60,
+                255,
+                60,
+                vm::END,
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_errors_with_line() {
+        let err = |source: &str| assemble(source).unwrap_err();
+        assert_eq!(
+            err("end\nblink 3"),
+            AsmError {
+                line: 2,
+                message: "unknown instruction `blink`".into()
+            }
+        );
+        assert_eq!(err("color 1 2").line, 1);
+        assert_eq!(err("fill 0 300").message, "invalid number `300`");
+        assert_eq!(err("wait 1\nnext").message, "`next` without `loop`");
+        assert_eq!(err("loop\nloop 2\nnext").line, 1);
+        assert_eq!(
+            err("sweep 0 10 0 10 -1 0 0 0 1").message,
+            "`-1` is out of range"
+        );
+        assert_eq!(
+            err("sweep 0 10 0 10 0.001 0 0 0 1").message,
+            "speed `0.001` doesn't move"
+        );
+        assert_eq!(
+            err("sweep 0 10 -100 10 1 0 0 0 1").message,
+            "position `-100` is too far from the strip"
+        );
+        assert_eq!(err("loop\nloop\nloop\nloop\nloop").line, 5);
+    }
+
+    #[test]
+    fn wave_program_matches_the_wave() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("programs/wave.tla");
+        let code = load(path).unwrap();
+        let mut program = vm::Vm::new(&code);
+        let mut wave = Wave::new();
+
+        let mut expected = [Color16::default(); NUM_LEDS];
+        let mut actual = [Color16::default(); NUM_LEDS];
+        let mut running = (true, true);
+        for ms in (0..3000).step_by(3) {
+            let t = Duration::from_millis(ms);
+            running = (
+                program.render(t, &mut actual),
+                wave.render(t, &mut expected),
+            );
+            for (a, e) in actual.iter().zip(&expected) {
+                // the program can't express the speed exactly
+                assert!(a.r.abs_diff(e.r) < 257, "{} ms: {:?} {:?}", ms, a, e);
+            }
+        }
+        assert_eq!(running, (false, false));
+        assert_eq!(program.fault(), None);
+    }
+}
@@ -0,0 +1,29 @@
+//! Assembles an effect program into the bytecode the firmware runs.
+//!
+//! ```text
+//! cargo run --bin asm -- <program.tla> <output.bin>
+//! ```
+//!
+//! See [`trailer_light_host::asm`] for the format.
+
+use std::{env, error::Error, fs, process};
+
+use trailer_light_host::asm;
+
+const USAGE: &str = "usage: asm <program.tla> <output.bin>";
+
+fn run() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let [input, output] = <[String; 2]>::try_from(args).map_err(|_| USAGE.to_string())?;
+    let code = asm::load(&input)?;
+    fs::write(&output, &code)?;
+    println!("{} bytes", code.len());
+    Ok(())
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
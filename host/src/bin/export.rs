@@ -0,0 +1,204 @@
+//! Renders an animation into an image for reviewing lighting changes.
+//!
+//! ```text
+//! cargo run --bin export -- <effect> <output.gif|output.png> [--scale N] [--interval MS]
+//! ```
+//!
+//! `<effect>` is `show`, one of [`trailer_light_host::effects::NAMES`], or the
+//! path of an effect program (`.tla`, see [`trailer_light_host::asm`]).
+//!
+//! A `.gif` is an animation of the strip, sampled every `--interval`
+//! milliseconds (default 20, GIF delays are in units of 10 ms). A `.png` is a
+//! space-time image: each row is one frame, each LED `--scale` columns. Without
+//! `--interval` it has one row per frame written to the LEDs, with it the
+//! frames are sampled like for the GIF, so pauses show up in the image.
+//!
+//! Frames are timed like on the real strip: every write takes the transmit
+//! time of the LEDs plus the delays of the animation code.
+
+use std::{env, error::Error, fs::File, io::BufWriter, path::Path, process, time::Duration};
+
+use trailer_light_core::{
+    power::{ThrottleStats, FULL_SCALE},
+    sequencer::{Sequencer, SHOW},
+    vm::Vm,
+    TrailerLight, NUM_LEDS,
+};
+use trailer_light_host::{
+    asm, effects,
+    recorder::{Frame, Recorder},
+    timing::{SimClock, SimDelay},
+};
+
+fn usage() -> String {
+    format!(
+        "usage: export <show|{}|program.tla> <output.gif|output.png> [--scale N] [--interval MS]",
+        effects::NAMES.join("|")
+    )
+}
+
+struct Options {
+    effect: String,
+    output: String,
+    scale: usize,
+    interval: Option<Duration>,
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut args = env::args().skip(1);
+    let mut positional = Vec::new();
+    let mut scale = 8;
+    let mut interval = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--scale" | "--interval" => {
+                let value: u64 = args
+                    .next()
+                    .and_then(|v| v.parse().ok())
+                    .filter(|&v| v > 0)
+                    .ok_or_else(|| format!("{} needs a positive number", arg))?;
+                if arg == "--scale" {
+                    // GIFs are at most 65535 pixels wide
+                    if NUM_LEDS as u64 * value > u16::MAX.into() {
+                        return Err(format!(
+                            "--scale can be at most {}",
+                            u16::MAX as usize / NUM_LEDS
+                        ));
+                    }
+                    scale = value as usize;
+                } else {
+                    interval = Some(Duration::from_millis(value));
+                }
+            }
+            _ => positional.push(arg),
+        }
+    }
+    let [effect, output] = <[String; 2]>::try_from(positional).map_err(|_| usage())?;
+    Ok(Options {
+        effect,
+        output,
+        scale,
+        interval,
+    })
+}
+
+fn record(effect: &str) -> Result<(Recorder, ThrottleStats), String> {
+    let clock = SimClock::new();
+    let mut tl = TrailerLight::new(
+        Recorder::with_clock(clock.clone()),
+        SimDelay::new(clock.clone()),
+        clock,
+    );
+    tl.black();
+    match effect {
+        "show" => {
+            // the show, until the wave has played once
+            let mut sequencer = Sequencer::new(&SHOW);
+            sequencer.start(tl.now());
+            while tl.step(&mut sequencer)
+                && !(sequencer.current() == Some(SHOW.len() - 1) && sequencer.plays() > 0)
+            {
+            }
+        }
+        path if path.ends_with(".tla") => {
+            let code = asm::load(path).map_err(|e| e.to_string())?;
+            tl.run(&mut Vm::new(&code));
+        }
+        name => match effects::by_name(name) {
+            Some(mut effect) => tl.run(effect.as_mut()),
+            None => return Err(format!("unknown effect `{}`", effect)),
+        },
+    }
+    let stats = tl.throttle_stats();
+    Ok((tl.into_led(), stats))
+}
+
+/// Pixels of `frame` as RGB bytes, each LED `scale` pixels wide.
+fn pixels(frame: &Frame, scale: usize) -> Vec<u8> {
+    frame
+        .iter()
+        .flat_map(|c| [c.r, c.g, c.b].repeat(scale))
+        .collect()
+}
+
+fn write_png(path: &Path, frames: &[&Frame], scale: usize) -> Result<(), Box<dyn Error>> {
+    let mut encoder = png::Encoder::new(
+        BufWriter::new(File::create(path)?),
+        (NUM_LEDS * scale) as u32,
+        frames.len() as u32,
+    );
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut data = Vec::new();
+    for frame in frames {
+        data.extend(pixels(frame, scale));
+    }
+    encoder.write_header()?.write_image_data(&data)?;
+    Ok(())
+}
+
+fn write_gif(
+    path: &Path,
+    frames: &[&Frame],
+    scale: usize,
+    interval: Duration,
+) -> Result<(), Box<dyn Error>> {
+    let width = (NUM_LEDS * scale) as u16;
+    let height = scale as u16;
+    let mut encoder = gif::Encoder::new(BufWriter::new(File::create(path)?), width, height, &[])?;
+    encoder.set_repeat(gif::Repeat::Infinite)?;
+
+    let centis = (interval.as_millis() / 10).max(1) as u16;
+    // merge identical consecutive frames into one with a longer delay
+    let mut i = 0;
+    while i < frames.len() {
+        let mut n = 1;
+        while i + n < frames.len() && frames[i + n] == frames[i] {
+            n += 1;
+        }
+        let mut frame =
+            gif::Frame::from_rgb(width, height, &pixels(frames[i], scale).repeat(scale));
+        frame.delay = centis.saturating_mul(n as u16);
+        encoder.write_frame(&frame)?;
+        i += n;
+    }
+    Ok(())
+}
+
+fn run(options: Options) -> Result<(), Box<dyn Error>> {
+    let (recorder, stats) = record(&options.effect)?;
+    if stats.throttled_frames > 0 {
+        eprintln!(
+            "warning: {} of {} frames exceeded the power budget, dimmed down to {}%",
+            stats.throttled_frames,
+            stats.frames,
+            stats.min_scale as u32 * 100 / FULL_SCALE as u32
+        );
+    }
+    let path = Path::new(&options.output);
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("gif") => {
+            // GIF delays are in units of 10 ms
+            let centis = options.interval.map_or(2, |i| (i.as_millis() / 10).max(1));
+            let interval = Duration::from_millis(centis as u64 * 10);
+            write_gif(path, &recorder.sample(interval), options.scale, interval)
+        }
+        Some("png") => {
+            let frames = match options.interval {
+                Some(interval) => recorder.sample(interval),
+                None => recorder.frames().iter().collect(),
+            };
+            write_png(path, &frames, options.scale)
+        }
+        _ => Err("output must be a .gif or .png file".into()),
+    }
+}
+
+fn main() {
+    let result = parse_args().map_err(Into::into).and_then(run);
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        process::exit(2);
+    }
+}
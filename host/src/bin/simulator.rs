@@ -0,0 +1,106 @@
+//! Renders the trailer light animations in the terminal.
+//!
+//! Every frame written to the LEDs is printed as a row of ANSI truecolor
+//! blocks, at the speed the real strip would show it.
+//!
+//! ```text
+//! cargo run --bin simulator [show|<effect>|program.tla]
+//! ```
+//!
+//! `<effect>` is one of [`trailer_light_host::effects::NAMES`].
+
+use std::{
+    convert::Infallible,
+    env,
+    io::{self, Write},
+    process, thread,
+};
+
+use smart_leds::{SmartLedsWrite, RGB8};
+
+use trailer_light_core::{
+    effects::Scene,
+    sequencer::{Sequencer, Step, Until, SHOW},
+    vm::Vm,
+    TrailerLight,
+};
+use trailer_light_host::{
+    asm,
+    delay::StdDelay,
+    effects,
+    timing::{transmit_time, StdClock},
+};
+
+/// LED strip that prints every frame to the terminal.
+struct TerminalLeds {
+    out: io::Stdout,
+}
+
+impl SmartLedsWrite for TerminalLeds {
+    type Error = Infallible;
+    type Color = RGB8;
+
+    fn write<T, I>(&mut self, iterator: T) -> Result<(), Self::Error>
+    where
+        T: Iterator<Item = I>,
+        I: Into<Self::Color>,
+    {
+        let mut line = String::from("\r");
+        let mut num_leds = 0;
+        for c in iterator {
+            let c = c.into();
+            line += &format!("\x1b[38;2;{};{};{}m\u{2588}", c.r, c.g, c.b);
+            num_leds += 1;
+        }
+        line += "\x1b[0m";
+
+        // The terminal can't do anything sensible about errors here, so
+        // ignore them just like a disconnected strip would.
+        let mut out = self.out.lock();
+        let _ = out.write_all(line.as_bytes());
+        let _ = out.flush();
+
+        thread::sleep(transmit_time(num_leds));
+        Ok(())
+    }
+}
+
+fn main() {
+    let effect = env::args().nth(1).unwrap_or_else(|| "show".into());
+
+    let led = TerminalLeds { out: io::stdout() };
+    let mut tl = TrailerLight::new(led, StdDelay, StdClock::new());
+    tl.black();
+
+    match effect.as_str() {
+        "show" => {
+            let mut sequencer = Sequencer::new(&SHOW);
+            sequencer.start(tl.now());
+            tl.play(&mut sequencer);
+        }
+        "wave" => {
+            let steps: [Step; 1] = [Step::new(Scene::Wave, Until::Forever)];
+            let mut sequencer = Sequencer::new(&steps);
+            sequencer.start(tl.now());
+            tl.play(&mut sequencer);
+        }
+        path if path.ends_with(".tla") => match asm::load(path) {
+            Ok(code) => tl.run(&mut Vm::new(&code)),
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        },
+        name => match effects::by_name(name) {
+            Some(mut effect) => tl.run(effect.as_mut()),
+            None => {
+                eprintln!(
+                    "usage: simulator [show|{}|program.tla]",
+                    effects::NAMES.join("|")
+                );
+                process::exit(2);
+            }
+        },
+    }
+    println!();
+}
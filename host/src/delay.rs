@@ -0,0 +1,29 @@
+use std::{thread, time::Duration};
+
+use embedded_hal::blocking::delay::{DelayMs, DelayUs};
+
+/// Delay backed by `thread::sleep`.
+pub struct StdDelay;
+
+impl DelayMs<u16> for StdDelay {
+    fn delay_ms(&mut self, ms: u16) {
+        thread::sleep(Duration::from_millis(ms.into()));
+    }
+}
+
+impl DelayUs<u16> for StdDelay {
+    fn delay_us(&mut self, us: u16) {
+        thread::sleep(Duration::from_micros(us.into()));
+    }
+}
+
+/// Delay that returns immediately, for running animations as fast as possible.
+pub struct NoDelay;
+
+impl DelayMs<u16> for NoDelay {
+    fn delay_ms(&mut self, _ms: u16) {}
+}
+
+impl DelayUs<u16> for NoDelay {
+    fn delay_us(&mut self, _us: u16) {}
+}
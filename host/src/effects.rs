@@ -0,0 +1,50 @@
+//! Looking up the built-in effects by the names used on the command line.
+
+use std::time::Duration;
+
+use trailer_light_core::{
+    color::{Interpolation, SweepColors},
+    combinator::EffectExt,
+    effects::{self, Blink, EmergencyBrake, Sweep, TurnOn, Wave},
+    AnimationContext, Color, Effect,
+};
+
+/// Names of the effects [`by_name`] knows.
+pub const NAMES: [&str; 8] = [
+    "blink",
+    "turn-on",
+    "wave",
+    "white-hot",
+    "brake",
+    "scanner",
+    "comets",
+    "rain",
+];
+
+/// How long the effects that never finish on their own are played.
+const ENDLESS: Duration = Duration::from_secs(10);
+
+pub fn by_name(name: &str) -> Option<Box<dyn Effect>> {
+    Some(match name {
+        "blink" => Box::new(Blink),
+        "turn-on" => Box::new(TurnOn::new()),
+        "wave" => Box::new(Wave::new()),
+        "white-hot" => Box::new(white_hot()),
+        "brake" => Box::new(EmergencyBrake),
+        "scanner" => Box::new(effects::scanner().take_for(ENDLESS)),
+        "comets" => Box::new(effects::comets().take_for(ENDLESS)),
+        "rain" => Box::new(effects::rain().take_for(ENDLESS)),
+        _ => return None,
+    })
+}
+
+/// The wave with a white-hot highlight over a red base.
+fn white_hot() -> Sweep {
+    let ctx = AnimationContext::new(-3.0, 32.0, 43.0 / 3.0, 0.0, 0.0, 0.0, 6.0);
+    Sweep::new(ctx).colors(SweepColors {
+        base: Color::new(60, 0, 0),
+        target: Color::new(60, 0, 0),
+        highlight: Color::new(255, 255, 255),
+        interpolation: Interpolation::Hsv,
+    })
+}
@@ -0,0 +1,151 @@
+//! Simulated accelerometer, for running the brake detection on recorded or
+//! synthetic acceleration traces.
+
+use std::time::Duration;
+
+use embedded_hal::blocking::i2c::{Write, WriteRead};
+use trailer_light_core::imu::{Accel, Mpu6050};
+
+use crate::timing::SimClock;
+
+/// Accelerations over time, in milli-g.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Trace {
+    samples: Vec<(Duration, Accel)>,
+}
+
+impl Trace {
+    /// Parses lines of `milliseconds,x,y,z`. Empty lines and lines starting
+    /// with `#` are skipped.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut samples = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            let [ms, x, y, z] = fields[..] else {
+                return Err(format!("line {}: expected 4 fields", i + 1));
+            };
+            let num = |s: &str| {
+                s.parse::<i32>()
+                    .map_err(|e| format!("line {}: {}: {}", i + 1, s, e))
+            };
+            let ms = ms
+                .parse::<u64>()
+                .map_err(|e| format!("line {}: {}: {}", i + 1, ms, e))?;
+            samples.push((
+                Duration::from_millis(ms),
+                Accel::new(num(x)?, num(y)?, num(z)?),
+            ));
+        }
+        Ok(samples.into_iter().collect())
+    }
+
+    pub fn samples(&self) -> &[(Duration, Accel)] {
+        &self.samples
+    }
+
+    /// The last sample at or before `t`, the first one before the trace
+    /// starts.
+    pub fn at(&self, t: Duration) -> Accel {
+        let i = self.samples.partition_point(|(time, _)| *time <= t);
+        self.samples
+            .get(i.saturating_sub(1))
+            .map_or(Accel::default(), |(_, accel)| *accel)
+    }
+
+    /// Time of the last sample.
+    pub fn end(&self) -> Duration {
+        self.samples.last().map_or(Duration::ZERO, |(t, _)| *t)
+    }
+}
+
+impl FromIterator<(Duration, Accel)> for Trace {
+    /// Collects samples, sorting them by time.
+    fn from_iter<I: IntoIterator<Item = (Duration, Accel)>>(iter: I) -> Self {
+        let mut samples: Vec<_> = iter.into_iter().collect();
+        samples.sort_by_key(|(t, _)| *t);
+        Trace { samples }
+    }
+}
+
+/// Nobody answered at the address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Nack;
+
+/// I2C bus with an MPU-6050 on it that measures a [`Trace`] at the time of a
+/// [`SimClock`].
+pub struct SimMpu6050 {
+    registers: [u8; 128],
+    trace: Trace,
+    clock: SimClock,
+}
+
+const ACCEL_CONFIG: usize = 0x1c;
+const ACCEL_XOUT_H: usize = 0x3b;
+const PWR_MGMT_1: usize = 0x6b;
+const WHO_AM_I: usize = 0x75;
+
+impl SimMpu6050 {
+    pub fn new(trace: Trace, clock: SimClock) -> Self {
+        let mut registers = [0; 128];
+        registers[WHO_AM_I] = 0x68;
+        // asleep after reset
+        registers[PWR_MGMT_1] = 0x40;
+        SimMpu6050 {
+            registers,
+            trace,
+            clock,
+        }
+    }
+
+    pub fn register(&self, register: u8) -> u8 {
+        self.registers[register as usize]
+    }
+
+    fn check(&self, address: u8) -> Result<(), Nack> {
+        match address == Mpu6050::<SimMpu6050>::ADDRESS {
+            true => Ok(()),
+            false => Err(Nack),
+        }
+    }
+
+    fn measure(&mut self) {
+        // a sleeping sensor doesn't update its measurements
+        if self.registers[PWR_MGMT_1] & 0x40 != 0 {
+            return;
+        }
+        let counts_per_g = 16_384 >> ((self.registers[ACCEL_CONFIG] >> 3) & 3);
+        let accel = self.trace.at(self.clock.now());
+        for (i, v) in [accel.x, accel.y, accel.z].into_iter().enumerate() {
+            let raw = (v * counts_per_g / 1000).clamp(i16::MIN as i32, i16::MAX as i32) as i16;
+            self.registers[ACCEL_XOUT_H + 2 * i..][..2].copy_from_slice(&raw.to_be_bytes());
+        }
+    }
+}
+
+impl Write for SimMpu6050 {
+    type Error = Nack;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Nack> {
+        self.check(address)?;
+        if let [register, values @ ..] = bytes {
+            self.registers[*register as usize..][..values.len()].copy_from_slice(values);
+        }
+        Ok(())
+    }
+}
+
+impl WriteRead for SimMpu6050 {
+    type Error = Nack;
+
+    fn write_read(&mut self, address: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), Nack> {
+        self.check(address)?;
+        self.measure();
+        let register = bytes[0] as usize;
+        buffer.copy_from_slice(&self.registers[register..][..buffer.len()]);
+        Ok(())
+    }
+}
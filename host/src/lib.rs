@@ -0,0 +1,10 @@
+//! Host side helpers for running the trailer light code on the development
+//! machine.
+
+pub mod asm;
+pub mod delay;
+pub mod effects;
+pub mod imu;
+pub mod recorder;
+pub mod snapshot;
+pub mod timing;
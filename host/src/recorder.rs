@@ -0,0 +1,110 @@
+use std::{convert::Infallible, time::Duration};
+
+use smart_leds::SmartLedsWrite;
+use trailer_light_core::Color;
+
+use crate::timing::{transmit_time, SimClock};
+
+pub type Frame = Vec<Color>;
+
+/// LED strip that keeps every frame written to it.
+///
+/// Each frame is stamped with the time of its [`SimClock`] when it was
+/// written, and writing advances the clock by the time the real strip would
+/// need to receive the frame.
+#[derive(Default)]
+pub struct Recorder {
+    clock: SimClock,
+    frames: Vec<Frame>,
+    times: Vec<Duration>,
+}
+
+impl Recorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_clock(clock: SimClock) -> Self {
+        Recorder {
+            clock,
+            ..Self::default()
+        }
+    }
+
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    /// When each frame was written.
+    pub fn times(&self) -> &[Duration] {
+        &self.times
+    }
+
+    /// Current time of the recorder's clock, i.e. the end of the recording.
+    pub fn now(&self) -> Duration {
+        self.clock.now()
+    }
+
+    /// Picks the frame shown at every `interval` from the first frame until
+    /// the end of the recording.
+    pub fn sample(&self, interval: Duration) -> Vec<&Frame> {
+        assert!(!interval.is_zero());
+        let mut sampled = Vec::new();
+        let Some(&start) = self.times.first() else {
+            return sampled;
+        };
+        let mut current = 0;
+        let mut t = start;
+        while t < self.now() {
+            while current + 1 < self.times.len() && self.times[current + 1] <= t {
+                current += 1;
+            }
+            sampled.push(&self.frames[current]);
+            t += interval;
+        }
+        sampled
+    }
+}
+
+impl SmartLedsWrite for Recorder {
+    type Error = Infallible;
+    type Color = Color;
+
+    fn write<T, I>(&mut self, iterator: T) -> Result<(), Self::Error>
+    where
+        T: Iterator<Item = I>,
+        I: Into<Self::Color>,
+    {
+        let frame: Frame = iterator.map(Into::into).collect();
+        self.times.push(self.clock.now());
+        self.clock.advance(transmit_time(frame.len()));
+        self.frames.push(frame);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(r: u8) -> [Color; 1] {
+        [Color::new(r, 0, 0)]
+    }
+
+    #[test]
+    fn sample_holds_frames_until_the_next_write() {
+        let clock = SimClock::new();
+        let mut rec = Recorder::with_clock(clock.clone());
+        rec.write(frame(0).into_iter()).unwrap();
+        clock.advance(Duration::from_millis(10));
+        rec.write(frame(1).into_iter()).unwrap();
+        clock.advance(Duration::from_millis(10));
+
+        let sampled: Vec<u8> = rec
+            .sample(Duration::from_millis(4))
+            .iter()
+            .map(|f| f[0].r)
+            .collect();
+        assert_eq!(sampled, [0, 0, 0, 1, 1, 1]);
+    }
+}
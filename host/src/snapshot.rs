@@ -0,0 +1,288 @@
+//! Golden-frame snapshots of recorded animations.
+//!
+//! A snapshot is a text file with one line per frame:
+//!
+//! ```text
+//! 0042: 0a0000*27 1e0000 960000*2 1e0000 0a0000*27
+//! ```
+//!
+//! Each LED is written as `rrggbb`, `*n` repeats a color `n` times. Having
+//! one frame per line keeps the diffs of changed snapshots readable.
+
+use std::{env, fmt, fs, path::Path};
+
+use trailer_light_core::Color;
+
+use crate::recorder::Frame;
+
+const HEADER: &str = "# trailer-light snapshot v1";
+
+/// Set this environment variable to rewrite snapshots instead of comparing
+/// against them.
+pub const UPDATE_ENV: &str = "UPDATE_SNAPSHOTS";
+
+/// Maximum number of differences listed when a snapshot doesn't match.
+const MAX_REPORTED: usize = 20;
+
+pub fn encode(frames: &[Frame]) -> String {
+    let mut out = String::from(HEADER);
+    out.push('\n');
+    for (i, frame) in frames.iter().enumerate() {
+        out += &format!("{:04}:", i);
+        let mut leds = frame.iter().peekable();
+        while let Some(c) = leds.next() {
+            let mut n = 1;
+            while leds.next_if_eq(&c).is_some() {
+                n += 1;
+            }
+            out += &format!(" {:02x}{:02x}{:02x}", c.r, c.g, c.b);
+            if n > 1 {
+                out += &format!("*{}", n);
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+#[derive(Debug)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+pub fn decode(s: &str) -> Result<Vec<Frame>, ParseError> {
+    let mut frames = Vec::new();
+    for (i, line) in s.lines().enumerate() {
+        let err = |message: String| ParseError {
+            line: i + 1,
+            message,
+        };
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (index, leds) = line
+            .split_once(':')
+            .ok_or_else(|| err("missing frame index".into()))?;
+        if index.parse() != Ok(frames.len()) {
+            return Err(err(format!(
+                "expected frame {}, found `{}`",
+                frames.len(),
+                index
+            )));
+        }
+
+        let mut frame = Frame::new();
+        for token in leds.split_whitespace() {
+            let (color, n) = match token.split_once('*') {
+                Some((color, n)) => {
+                    let n = n
+                        .parse()
+                        .map_err(|_| err(format!("invalid count in `{}`", token)))?;
+                    (color, n)
+                }
+                None => (token, 1),
+            };
+            let rgb = (color.len() == 6)
+                .then(|| u32::from_str_radix(color, 16).ok())
+                .flatten()
+                .ok_or_else(|| err(format!("invalid color `{}`", color)))?;
+            let c = Color::new((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8);
+            frame.extend(std::iter::repeat_n(c, n));
+        }
+        frames.push(frame);
+    }
+    Ok(frames)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Difference {
+    FrameCount {
+        expected: usize,
+        actual: usize,
+    },
+    LedCount {
+        frame: usize,
+        expected: usize,
+        actual: usize,
+    },
+    Led {
+        frame: usize,
+        led: usize,
+        expected: Color,
+        actual: Color,
+    },
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Difference::FrameCount { expected, actual } => {
+                write!(f, "expected {} frames, got {}", expected, actual)
+            }
+            Difference::LedCount {
+                frame,
+                expected,
+                actual,
+            } => {
+                write!(
+                    f,
+                    "frame {}: expected {} LEDs, got {}",
+                    frame, expected, actual
+                )
+            }
+            Difference::Led {
+                frame,
+                led,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "frame {}, LED {}: expected {:02x}{:02x}{:02x}, got {:02x}{:02x}{:02x}",
+                frame, led, expected.r, expected.g, expected.b, actual.r, actual.g, actual.b
+            ),
+        }
+    }
+}
+
+/// Lists every frame and LED that differs between `expected` and `actual`.
+pub fn diff(expected: &[Frame], actual: &[Frame]) -> Vec<Difference> {
+    let mut differences = Vec::new();
+    for (frame, (e, a)) in expected.iter().zip(actual).enumerate() {
+        if e.len() != a.len() {
+            differences.push(Difference::LedCount {
+                frame,
+                expected: e.len(),
+                actual: a.len(),
+            });
+            continue;
+        }
+        for (led, (&expected, &actual)) in e.iter().zip(a).enumerate() {
+            if expected != actual {
+                differences.push(Difference::Led {
+                    frame,
+                    led,
+                    expected,
+                    actual,
+                });
+            }
+        }
+    }
+    if expected.len() != actual.len() {
+        differences.push(Difference::FrameCount {
+            expected: expected.len(),
+            actual: actual.len(),
+        });
+    }
+    differences
+}
+
+/// Compares `frames` against the snapshot stored at `path`.
+///
+/// Panics with a list of the changed frames and LEDs if they don't match.
+/// With `UPDATE_SNAPSHOTS` set the snapshot is rewritten instead.
+pub fn assert_snapshot(path: impl AsRef<Path>, frames: &[Frame]) {
+    let path = path.as_ref();
+    if env::var_os(UPDATE_ENV).is_some() {
+        fs::write(path, encode(frames)).unwrap();
+        return;
+    }
+
+    let expected = match fs::read_to_string(path) {
+        Ok(s) => s,
+        Err(e) => panic!(
+            "can't read snapshot {} ({}), run with {}=1 to create it",
+            path.display(),
+            e,
+            UPDATE_ENV
+        ),
+    };
+    let expected =
+        decode(&expected).unwrap_or_else(|e| panic!("invalid snapshot {}: {}", path.display(), e));
+
+    let differences = diff(&expected, frames);
+    if differences.is_empty() {
+        return;
+    }
+    let mut msg = format!(
+        "{} differences to snapshot {} (run with {}=1 to accept them):\n",
+        differences.len(),
+        path.display(),
+        UPDATE_ENV
+    );
+    for d in differences.iter().take(MAX_REPORTED) {
+        msg += &format!("  {}\n", d);
+    }
+    if differences.len() > MAX_REPORTED {
+        msg += "  ...\n";
+    }
+    panic!("{}", msg);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frames() -> Vec<Frame> {
+        vec![
+            vec![Color::new(0, 0, 0); 4],
+            vec![
+                Color::new(10, 0, 0),
+                Color::new(255, 1, 2),
+                Color::new(255, 1, 2),
+                Color::new(10, 0, 0),
+            ],
+        ]
+    }
+
+    #[test]
+    fn encodes_runs() {
+        assert_eq!(
+            encode(&frames()),
+            "# trailer-light snapshot v1\n0000: 000000*4\n0001: 0a0000 ff0102*2 0a0000\n"
+        );
+    }
+
+    #[test]
+    fn roundtrip() {
+        assert_eq!(decode(&encode(&frames())).unwrap(), frames());
+    }
+
+    #[test]
+    fn rejects_bad_colors() {
+        let e = decode("0000: 00000\n").unwrap_err();
+        assert_eq!(e.line, 1);
+        assert!(decode("0000: 000000*x\n").is_err());
+        assert!(decode("0001: 000000\n").is_err());
+    }
+
+    #[test]
+    fn diff_reports_changed_leds() {
+        let mut changed = frames();
+        changed[1][2] = Color::new(0, 0, 0);
+        changed.push(vec![]);
+        assert_eq!(
+            diff(&frames(), &changed),
+            [
+                Difference::Led {
+                    frame: 1,
+                    led: 2,
+                    expected: Color::new(255, 1, 2),
+                    actual: Color::new(0, 0, 0),
+                },
+                Difference::FrameCount {
+                    expected: 2,
+                    actual: 3
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,88 @@
+use std::{cell::Cell, rc::Rc, time::Duration};
+
+use embedded_hal::blocking::delay::{DelayMs, DelayUs};
+use trailer_light_core::time::{Clock, Instant};
+
+// WS2812: 24 bits per LED at 800 kHz, followed by a >50 µs reset pulse.
+const LED_TRANSMIT_TIME: Duration = Duration::from_micros(30);
+const RESET_TIME: Duration = Duration::from_micros(50);
+
+/// Time it takes to send a frame of `num_leds` to the strip.
+pub fn transmit_time(num_leds: usize) -> Duration {
+    LED_TRANSMIT_TIME * num_leds as u32 + RESET_TIME
+}
+
+/// Virtual time, for replaying animations with their real timing but without
+/// waiting for it.
+///
+/// Clones share the same time.
+#[derive(Clone, Default)]
+pub struct SimClock(Rc<Cell<Duration>>);
+
+impl SimClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn now(&self) -> Duration {
+        self.0.get()
+    }
+
+    pub fn advance(&self, d: Duration) {
+        self.0.set(self.0.get() + d);
+    }
+}
+
+impl Clock for SimClock {
+    fn now(&self) -> Instant {
+        Instant::from_micros(self.now().as_micros() as u64)
+    }
+}
+
+/// Wall clock time since the clock was created.
+pub struct StdClock {
+    start: std::time::Instant,
+}
+
+impl StdClock {
+    pub fn new() -> Self {
+        StdClock {
+            start: std::time::Instant::now(),
+        }
+    }
+}
+
+impl Default for StdClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for StdClock {
+    fn now(&self) -> Instant {
+        Instant::from_micros(self.start.elapsed().as_micros() as u64)
+    }
+}
+
+/// Delay that advances a [`SimClock`] instead of sleeping.
+pub struct SimDelay {
+    clock: SimClock,
+}
+
+impl SimDelay {
+    pub fn new(clock: SimClock) -> Self {
+        SimDelay { clock }
+    }
+}
+
+impl DelayMs<u16> for SimDelay {
+    fn delay_ms(&mut self, ms: u16) {
+        self.clock.advance(Duration::from_millis(ms.into()));
+    }
+}
+
+impl DelayUs<u16> for SimDelay {
+    fn delay_us(&mut self, us: u16) {
+        self.clock.advance(Duration::from_micros(us.into()));
+    }
+}
@@ -0,0 +1,96 @@
+//! Brake detection from the accelerometer to the LEDs, on a simulated
+//! MPU-6050 replaying an acceleration trace.
+
+use std::time::Duration;
+
+use trailer_light_core::{
+    brake::{BrakeLight, FADE, HOLD},
+    braking::{Axis, BrakeDetector, BrakeEvent},
+    effect::Runner,
+    effects::Solid,
+    imu::{Accel, Accelerometer, Mpu6050},
+    Color, TrailerLight,
+};
+use trailer_light_host::{
+    imu::{SimMpu6050, Trace},
+    recorder::Recorder,
+    timing::{SimClock, SimDelay},
+};
+
+const BLUE: Color = Color::new(0, 0, 255);
+const RED: Color = Color::new(255, 0, 0);
+
+/// 100 Hz samples of cruising for 3 s, braking at 0.35 g for 1.5 s and
+/// cruising again, with some road noise.
+fn stop() -> Trace {
+    let mut noise = 0x2545_f491_u32;
+    (0..800)
+        .map(|i| {
+            noise ^= noise << 13;
+            noise ^= noise >> 17;
+            noise ^= noise << 5;
+            let n = (noise % 121) as i32 - 60;
+            let forward = if (300..450).contains(&i) { -350 } else { 0 };
+            let t = Duration::from_millis(i * 10);
+            (t, Accel::new(forward + n, n / 3, 1000 - n))
+        })
+        .collect()
+}
+
+#[test]
+fn parses_traces() {
+    let trace = Trace::parse("# ms,x,y,z\n0,1,2,1000\n\n10, -350, 0, 990\n").unwrap();
+    assert_eq!(trace.samples().len(), 2);
+    assert_eq!(trace.at(Duration::from_millis(5)), Accel::new(1, 2, 1000));
+    assert_eq!(trace.at(Duration::from_secs(1)), Accel::new(-350, 0, 990));
+    assert!(Trace::parse("0,1,2").is_err());
+    assert!(Trace::parse("0,1,2,x").is_err());
+}
+
+#[test]
+fn deceleration_turns_on_the_brake_light() {
+    let trace = stop();
+    let end = trace.end();
+    let clock = SimClock::new();
+    let mut tl = TrailerLight::new(
+        Recorder::with_clock(clock.clone()),
+        SimDelay::new(clock.clone()),
+        clock.clone(),
+    );
+    let bus = SimMpu6050::new(trace, clock.clone());
+    let mut mpu = Mpu6050::new(bus, Mpu6050::<SimMpu6050>::ADDRESS).unwrap();
+    let mut detector = BrakeDetector::new(Axis::X);
+    let mut runner = Runner::new();
+    runner.start(Solid(BLUE), tl.now());
+    let mut light = BrakeLight::new(runner);
+
+    let mut events = Vec::new();
+    while clock.now() < end {
+        let now = tl.now();
+        if let Some(event) = detector.update(mpu.accel().unwrap(), now) {
+            light.brake(event == BrakeEvent::Applied, now);
+            events.push((clock.now(), event));
+        }
+        tl.step(&mut light);
+    }
+
+    assert_eq!(events.len(), 2, "{:?}", events);
+    let (applied, released) = (events[0].0, events[1].0);
+    assert_eq!(events[0].1, BrakeEvent::Applied);
+    assert!(applied > Duration::from_millis(3000) && applied < Duration::from_millis(3150));
+    assert!(released > Duration::from_millis(4500) && released < Duration::from_millis(4650));
+
+    let rec = tl.into_led();
+    let shown_at = |t: Duration| {
+        let i = rec.times().partition_point(|time| *time <= t);
+        &rec.frames()[i - 1]
+    };
+    assert!(shown_at(applied - Duration::from_millis(10))
+        .iter()
+        .all(|c| *c == BLUE));
+    for t in [applied + Duration::from_millis(10), released + HOLD / 2] {
+        assert!(shown_at(t).iter().all(|c| *c == RED));
+    }
+    let faded = released + HOLD + FADE.duration + Duration::from_millis(10);
+    assert!(shown_at(faded).iter().all(|c| *c == BLUE));
+}
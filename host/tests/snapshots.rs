@@ -0,0 +1,47 @@
+//! Golden-frame tests for the built-in animations.
+//!
+//! If an animation is changed on purpose, regenerate the snapshots with
+//! `UPDATE_SNAPSHOTS=1 cargo test` and review the diff.
+
+use std::path::PathBuf;
+
+use trailer_light_core::{
+    effects::{TurnOn, Wave},
+    TrailerLight,
+};
+use trailer_light_host::{
+    recorder::Recorder,
+    snapshot::assert_snapshot,
+    timing::{SimClock, SimDelay},
+};
+
+/// Runs `effect` on simulated time, so the frames don't depend on the speed
+/// of the machine running the tests.
+fn record(effect: impl FnOnce(&mut TrailerLight<Recorder, SimDelay, SimClock>)) -> Recorder {
+    let clock = SimClock::new();
+    let mut tl = TrailerLight::new(
+        Recorder::with_clock(clock.clone()),
+        SimDelay::new(clock.clone()),
+        clock,
+    );
+    effect(&mut tl);
+    tl.into_led()
+}
+
+fn snapshot_path(name: &str) -> PathBuf {
+    [env!("CARGO_MANIFEST_DIR"), "tests", "snapshots", name]
+        .iter()
+        .collect()
+}
+
+#[test]
+fn turn_on_animation() {
+    let rec = record(|tl| tl.run(&mut TurnOn::new()));
+    assert_snapshot(snapshot_path("turn_on_animation.snap"), rec.frames());
+}
+
+#[test]
+fn wave_animation() {
+    let rec = record(|tl| tl.run(&mut Wave::new()));
+    assert_snapshot(snapshot_path("wave_animation.snap"), rec.frames());
+}
@@ -0,0 +1,248 @@
+use core::time::Duration;
+
+use crate::{color::SweepColors, easing::Easing, kernel::Kernel, Color16};
+
+/// A single highlight moving over a strip of LEDs.
+///
+/// LEDs the highlight has already passed are set to the target brightness,
+/// the ones in front of it keep the base brightness.
+///
+/// The highlight moves with `speed` LEDs per second on average, at constant
+/// speed unless an [`easing`](Self::easing) is set. Brightness values are
+/// given in 8 bit units (`0.0..=255.0`), the output is 16 bit to keep the
+/// fractional part for dithering.
+pub struct AnimationContext {
+    start_pos: f32,
+    end_pos: f32,
+    speed: f32, // LEDs per second
+    asc: bool,
+    bb: f32, // base brightness
+    tb: f32, // target brightness
+    hb: f32, // highlight brightness
+    hw: f32, // highlight width
+    kernel: Kernel,
+    easing: Easing,
+}
+
+impl AnimationContext {
+    pub fn new(
+        start_pos: f32,
+        end_pos: f32,
+        speed: f32,
+        bb: f32,
+        tb: f32,
+        hb: f32,
+        hw: f32,
+    ) -> AnimationContext {
+        assert!(bb <= tb && tb <= hb);
+        assert!(speed > 0.0);
+        AnimationContext {
+            start_pos,
+            end_pos,
+            speed,
+            asc: start_pos < end_pos,
+            bb,
+            tb,
+            hb,
+            hw,
+            kernel: Kernel::Triangle,
+            easing: Easing::Linear,
+        }
+    }
+
+    /// Shape of the highlight, a triangle by default.
+    pub fn kernel(mut self, kernel: Kernel) -> Self {
+        self.kernel = kernel;
+        self
+    }
+
+    /// Motion of the highlight from `start_pos` to `end_pos`, the duration
+    /// stays the same.
+    pub fn easing(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+
+    /// Time the highlight takes from `start_pos` to `end_pos`.
+    pub fn duration(&self) -> Duration {
+        let distance = if self.asc {
+            self.end_pos - self.start_pos
+        } else {
+            self.start_pos - self.end_pos
+        };
+        Duration::from_micros((distance / self.speed * 1_000_000.0) as u64)
+    }
+
+    /// Position of the highlight `t` after the start, it stops at `end_pos`.
+    pub fn position(&self, t: Duration) -> f32 {
+        if self.easing != Easing::Linear {
+            let duration = self.duration();
+            if t >= duration {
+                return self.end_pos;
+            }
+            let progress = self
+                .easing
+                .apply(t.as_micros() as f32 / duration.as_micros() as f32);
+            return self.start_pos + (self.end_pos - self.start_pos) * progress;
+        }
+        let distance = self.speed * (t.as_micros() as f32 / 1_000_000.0);
+        if self.asc {
+            (self.start_pos + distance).min(self.end_pos)
+        } else {
+            (self.start_pos - distance).max(self.end_pos)
+        }
+    }
+
+    /// Writes the values at `t` after the start into `v`. Returns `false`
+    /// once the highlight has reached `end_pos`.
+    pub fn render(&self, t: Duration, v: &mut [u16]) -> bool {
+        let hpos = self.position(t);
+        self.calc_values(hpos, v);
+        hpos != self.end_pos
+    }
+
+    /// Like [`render`](Self::render), but in the given colors instead of
+    /// the brightness values.
+    pub fn render_colors(&self, t: Duration, colors: &SweepColors, c: &mut [Color16]) -> bool {
+        let hpos = self.position(t);
+        for (i, c) in c.iter_mut().enumerate() {
+            let (passed, highlight) = self.calc_parts(hpos, i);
+            *c = colors.at(passed, highlight);
+        }
+        hpos != self.end_pos
+    }
+
+    pub fn calc_values(&self, hpos: f32, v: &mut [u16]) {
+        for (i, v) in v.iter_mut().enumerate() {
+            *v = (self.calc_value(hpos, i) * 257.0) as u16;
+        }
+    }
+
+    /// Whether the highlight at `hpos` has passed the LED at index `pos`, and
+    /// how much the highlight lights it, `u16::MAX` at its center.
+    pub fn calc_parts(&self, hpos: f32, pos: usize) -> (bool, u16) {
+        let pos = pos as f32;
+        let passed = self.asc && hpos >= pos || !self.asc && hpos <= pos;
+        let pos_diff = hpos - pos;
+        let pos_diff = if pos_diff < 0.0 { -pos_diff } else { pos_diff };
+        let highlight = if pos_diff < self.hw {
+            (self.kernel.apply(pos_diff / self.hw) * u16::MAX as f32) as u16
+        } else {
+            0
+        };
+        (passed, highlight)
+    }
+
+    pub fn calc_value(
+        &self,
+        hpos: f32,
+        pos: usize, // led position (index)
+    ) -> f32 {
+        let pos = pos as f32;
+        let use_tb = self.asc && hpos >= pos || !self.asc && hpos <= pos;
+        let ambient = self.bb + (self.tb - self.bb) * use_tb as u8 as f32;
+
+        let pos_diff = hpos - pos;
+        let pos_diff = if pos_diff < 0.0 { -pos_diff } else { pos_diff };
+        let highlight = if pos_diff < self.hw {
+            self.hb * self.kernel.apply(pos_diff / self.hw)
+        } else {
+            0.0
+        };
+        if highlight > ambient {
+            highlight
+        } else {
+            ambient
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn highlight_peaks_at_its_position() {
+        let ctx = AnimationContext::new(0.0, 10.0, 1.0, 0.0, 10.0, 100.0, 2.0);
+        assert_eq!(ctx.calc_value(4.0, 4), 100.0);
+        assert_eq!(ctx.calc_value(4.0, 5), 50.0);
+        assert_eq!(ctx.calc_value(4.0, 3), 50.0);
+    }
+
+    #[test]
+    fn ambient_depends_on_direction() {
+        let asc = AnimationContext::new(0.0, 10.0, 1.0, 5.0, 10.0, 100.0, 1.0);
+        assert_eq!(asc.calc_value(4.0, 0), 10.0);
+        assert_eq!(asc.calc_value(4.0, 9), 5.0);
+
+        let desc = AnimationContext::new(10.0, 0.0, 1.0, 5.0, 10.0, 100.0, 1.0);
+        assert_eq!(desc.calc_value(4.0, 0), 5.0);
+        assert_eq!(desc.calc_value(4.0, 9), 10.0);
+    }
+
+    #[test]
+    fn moves_with_speed() {
+        let ctx = AnimationContext::new(0.0, 10.0, 4.0, 0.0, 10.0, 100.0, 1.0);
+        assert_eq!(ctx.duration(), Duration::from_millis(2500));
+        assert_eq!(ctx.position(Duration::ZERO), 0.0);
+        assert_eq!(ctx.position(Duration::from_millis(500)), 2.0);
+        assert_eq!(ctx.position(Duration::from_secs(10)), 10.0);
+
+        let desc = AnimationContext::new(10.0, 0.0, 4.0, 0.0, 10.0, 100.0, 1.0);
+        assert_eq!(desc.position(Duration::from_millis(500)), 8.0);
+        assert_eq!(desc.position(Duration::from_secs(10)), 0.0);
+    }
+
+    #[test]
+    fn render_stops_at_end_pos() {
+        let ctx = AnimationContext::new(0.0, 2.0, 1.0, 0.0, 10.0, 100.0, 1.0);
+        let mut v = [0; 3];
+        assert!(ctx.render(Duration::from_millis(1999), &mut v));
+        assert!(!ctx.render(Duration::from_secs(2), &mut v));
+        assert_eq!(v, [10 * 257, 10 * 257, 100 * 257]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_base_above_target() {
+        AnimationContext::new(0.0, 1.0, 1.0, 20.0, 10.0, 100.0, 1.0);
+    }
+
+    #[test]
+    fn colors_fade_from_the_highlight() {
+        use crate::{color::Interpolation, Color};
+
+        let ctx = AnimationContext::new(0.0, 10.0, 1000.0, 0.0, 0.0, 0.0, 2.0);
+        let colors = SweepColors {
+            base: Color::new(0, 0, 0),
+            target: Color::new(255, 0, 0),
+            highlight: Color::new(255, 255, 255),
+            interpolation: Interpolation::Rgb,
+        };
+        let mut c = [Color16::default(); 10];
+        assert!(ctx.render_colors(Duration::from_millis(4), &colors, &mut c));
+        assert_eq!(c[0], Color16::new(u16::MAX, 0, 0));
+        assert_eq!(c[4], Color16::new(u16::MAX, u16::MAX, u16::MAX));
+        assert_eq!(c[5], Color16::new(32_767, 32_767, 32_767));
+        assert_eq!(c[9], Color16::new(0, 0, 0));
+    }
+
+    #[test]
+    fn easing_keeps_duration_and_ends() {
+        let ctx = AnimationContext::new(0.0, 10.0, 1.0, 0.0, 10.0, 100.0, 1.0)
+            .easing(Easing::EaseInOut);
+        assert_eq!(ctx.duration(), Duration::from_secs(10));
+        assert_eq!(ctx.position(Duration::ZERO), 0.0);
+        assert!(ctx.position(Duration::from_millis(2500)) < 2.5);
+        assert_eq!(ctx.position(Duration::from_secs(5)), 5.0);
+        assert_eq!(ctx.position(Duration::from_secs(10)), 10.0);
+    }
+
+    #[test]
+    fn kernel_shapes_the_highlight() {
+        let ctx = AnimationContext::new(0.0, 10.0, 1.0, 0.0, 0.0, 100.0, 2.0)
+            .kernel(Kernel::FlatTop);
+        assert_eq!(ctx.calc_value(4.0, 5), 100.0);
+        assert_eq!(ctx.calc_value(4.0, 6), 0.0);
+    }
+}
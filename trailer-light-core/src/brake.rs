@@ -0,0 +1,232 @@
+//! Brake light.
+//!
+//! [`BrakeLight`] wraps whatever [`Player`] drives the light, including the
+//! [indicators](crate::indicator), and overrides it with full red while the
+//! brake is applied. After the brake is released the red is held a little
+//! longer, so short taps are still seen, then fades back to the player,
+//! which has kept running underneath.
+//!
+//! The frame isn't checked against the power budget here, the
+//! [`PowerLimiter`](crate::power::PowerLimiter) dims it when it's written.
+
+use core::time::Duration;
+
+use crate::{
+    easing::Easing,
+    effect::{Frame, Player},
+    time::Instant,
+    transition::{crossfade, Transition},
+    Color16, NUM_LEDS,
+};
+
+/// Full red, the brightest the strip gets.
+pub const BRAKE_RED: Color16 = Color16::new(u16::MAX, 0, 0);
+
+/// How long the light stays red after the brake is released.
+pub const HOLD: Duration = Duration::from_millis(500);
+
+/// Fade from the brake light back to the player.
+pub const FADE: Transition = Transition::new(Duration::from_millis(300), Easing::EaseInOut);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    Off,
+    Applied,
+    Released(Instant),
+}
+
+/// Plays `P` with the brake light taking priority over it.
+pub struct BrakeLight<P> {
+    player: P,
+    state: State,
+    hold: Duration,
+    fade: Transition,
+}
+
+impl<P: Player> BrakeLight<P> {
+    pub fn new(player: P) -> Self {
+        BrakeLight {
+            player,
+            state: State::Off,
+            hold: HOLD,
+            fade: FADE,
+        }
+    }
+
+    /// How long the light stays red after the brake is released, [`HOLD`]
+    /// by default.
+    pub fn hold(mut self, hold: Duration) -> Self {
+        self.hold = hold;
+        self
+    }
+
+    /// How the light fades back to the player after the hold time, [`FADE`]
+    /// by default.
+    pub fn fade(mut self, fade: Transition) -> Self {
+        self.fade = fade;
+        self
+    }
+
+    /// Sets the state of the brake input at `now`. Applying it takes effect
+    /// with the next frame, also while fading back. Releasing it starts the
+    /// hold time, repeating either doesn't change anything.
+    pub fn brake(&mut self, applied: bool, now: Instant) {
+        self.state = match (applied, self.state) {
+            (true, _) => State::Applied,
+            (false, State::Applied) => State::Released(now),
+            (false, state) => state,
+        };
+    }
+
+    /// Turns the brake light off at once, without the hold time and fade,
+    /// e.g. when the whole light is turned off.
+    pub fn cancel(&mut self) {
+        self.state = State::Off;
+    }
+
+    /// Whether the brake light is showing, fully or fading out.
+    pub fn is_active(&self) -> bool {
+        self.state != State::Off
+    }
+
+    pub fn player(&mut self) -> &mut P {
+        &mut self.player
+    }
+
+    pub fn into_player(self) -> P {
+        self.player
+    }
+}
+
+impl<P: Player> Player for BrakeLight<P> {
+    /// Renders the player, then the brake light over it. Returns `false`
+    /// once the player has finished and the brake light is off.
+    fn render(&mut self, now: Instant, frame: &mut Frame) -> bool {
+        let running = self.player.render(now, frame);
+        let fading = match self.state {
+            State::Off => return running,
+            State::Applied => None,
+            State::Released(at) => (now - at).checked_sub(self.hold),
+        };
+        let weight = fading.map_or(0, |t| self.fade.weight(t));
+        crossfade(&[BRAKE_RED; NUM_LEDS], frame, weight);
+        // easings may reach full weight before the end, e.g. a bounce
+        if fading.is_some_and(|t| t >= self.fade.duration) {
+            self.state = State::Off;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Solid blue, finished after a second.
+    struct Blue;
+
+    const BLUE: Color16 = Color16::new(0, 0, 1000);
+
+    impl Player for Blue {
+        fn render(&mut self, now: Instant, frame: &mut Frame) -> bool {
+            *frame = [BLUE; NUM_LEDS];
+            now < at_ms(1000)
+        }
+    }
+
+    fn at_ms(ms: u64) -> Instant {
+        Instant::from_micros(ms * 1_000)
+    }
+
+    fn render(brake: &mut BrakeLight<Blue>, ms: u64) -> (bool, Frame) {
+        let mut frame = [Color16::default(); NUM_LEDS];
+        let running = brake.render(at_ms(ms), &mut frame);
+        (running, frame)
+    }
+
+    #[test]
+    fn overrides_the_player_while_applied() {
+        let mut brake = BrakeLight::new(Blue);
+        assert_eq!(render(&mut brake, 0).1, [BLUE; NUM_LEDS]);
+
+        brake.brake(true, at_ms(10));
+        assert_eq!(render(&mut brake, 10).1, [BRAKE_RED; NUM_LEDS]);
+        // keeps running after the player has finished
+        let (running, frame) = render(&mut brake, 5000);
+        assert!(running);
+        assert_eq!(frame, [BRAKE_RED; NUM_LEDS]);
+    }
+
+    #[test]
+    fn holds_then_fades_back() {
+        let fade = Transition::new(Duration::from_millis(100), Easing::Linear);
+        let mut brake = BrakeLight::new(Blue).fade(fade);
+        brake.brake(true, at_ms(0));
+        render(&mut brake, 0);
+        brake.brake(false, at_ms(100));
+        // releasing again doesn't restart the hold time
+        brake.brake(false, at_ms(300));
+
+        assert_eq!(render(&mut brake, 599).1[0], BRAKE_RED);
+        let (_, frame) = render(&mut brake, 650);
+        assert!(frame[0].r < BRAKE_RED.r && frame[0].r > BLUE.r);
+        assert!(frame[0].b > 0 && frame[0].b < BLUE.b);
+
+        assert_eq!(render(&mut brake, 700).1, [BLUE; NUM_LEDS]);
+        assert!(!brake.is_active());
+        assert!(render(&mut brake, 800).0);
+        assert!(!render(&mut brake, 1000).0);
+    }
+
+    #[test]
+    fn fades_for_the_whole_transition() {
+        let fade = Transition::new(Duration::from_millis(1100), Easing::Bounce);
+        let mut brake = BrakeLight::new(Blue).hold(Duration::ZERO).fade(fade);
+        brake.brake(true, at_ms(0));
+        brake.brake(false, at_ms(0));
+
+        // the bounce reaches the player halfway, then comes back
+        assert_eq!(render(&mut brake, 400).1[0], BLUE);
+        assert!(brake.is_active());
+        assert_ne!(render(&mut brake, 600).1[0], BLUE);
+
+        assert_eq!(render(&mut brake, 1100).1[0], BLUE);
+        assert!(!brake.is_active());
+    }
+
+    #[test]
+    fn applying_while_fading_cuts_back_to_red() {
+        let mut brake = BrakeLight::new(Blue).hold(Duration::ZERO);
+        brake.brake(true, at_ms(0));
+        brake.brake(false, at_ms(100));
+        assert_ne!(render(&mut brake, 200).1[0], BRAKE_RED);
+
+        brake.brake(true, at_ms(200));
+        assert_eq!(render(&mut brake, 200).1[0], BRAKE_RED);
+        assert!(brake.is_active());
+    }
+
+    #[test]
+    fn cancelling_skips_the_hold_and_fade() {
+        let mut brake = BrakeLight::new(Blue);
+        brake.brake(true, at_ms(0));
+        brake.cancel();
+        assert!(!brake.is_active());
+        assert_eq!(render(&mut brake, 0).1, [BLUE; NUM_LEDS]);
+    }
+
+    #[test]
+    fn covers_the_indicators() {
+        use crate::indicator::{Indicator, Indicators};
+
+        let mut light = BrakeLight::new(Indicators::new(Blue));
+        light.player().start(Indicator::Hazard, at_ms(0));
+        let mut frame = [Color16::default(); NUM_LEDS];
+        light.render(at_ms(0), &mut frame);
+        assert_ne!(frame, [BLUE; NUM_LEDS]);
+
+        light.brake(true, at_ms(0));
+        light.render(at_ms(0), &mut frame);
+        assert_eq!(frame, [BRAKE_RED; NUM_LEDS]);
+    }
+}
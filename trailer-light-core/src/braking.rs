@@ -0,0 +1,366 @@
+//! Detecting braking from the acceleration of the bike.
+//!
+//! The [`BrakeDetector`] takes samples of an
+//! [`Accelerometer`](crate::imu::Accelerometer) with their timestamps:
+//!
+//! 1. A low-pass filter smooths out road noise and vibration.
+//! 2. A much slower one estimates gravity. It's frozen while braking, so a
+//!    long stop doesn't leak into it.
+//! 3. What's left after subtracting gravity is projected onto the horizontal
+//!    direction the bike is heading in. Which way is horizontal comes from
+//!    the gravity estimate, so the sensor doesn't need to be mounted level
+//!    and riding uphill or downhill doesn't look like accelerating.
+//! 4. Thresholds with [`Hysteresis`] turn the deceleration into
+//!    [`BrakeEvent`]s, for [`BrakeLight::brake`](crate::brake::BrakeLight::brake).
+
+use core::time::Duration;
+
+use crate::{imu::Accel, time::Instant};
+
+/// Axis of the sensor pointing in the direction the bike is heading.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Axis {
+    X,
+    NegX,
+    Y,
+    NegY,
+    Z,
+    NegZ,
+}
+
+impl Axis {
+    const fn unit(self) -> Accel {
+        match self {
+            Axis::X => Accel::new(1, 0, 0),
+            Axis::NegX => Accel::new(-1, 0, 0),
+            Axis::Y => Accel::new(0, 1, 0),
+            Axis::NegY => Accel::new(0, -1, 0),
+            Axis::Z => Accel::new(0, 0, 1),
+            Axis::NegZ => Accel::new(0, 0, -1),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BrakeEvent {
+    Applied,
+    Released,
+}
+
+/// Thresholds with hysteresis turning a deceleration into brake events.
+///
+/// Used by the [`BrakeDetector`], and with the deceleration of a
+/// [`WheelSensor`](crate::wheel::WheelSensor).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Hysteresis {
+    apply: i32,
+    release: i32,
+    braking: bool,
+}
+
+impl Hysteresis {
+    /// Applies the brake from a deceleration of `apply` on and releases it
+    /// at `release` or below.
+    ///
+    /// # Panics
+    ///
+    /// If `release` is larger than `apply`.
+    pub const fn new(apply: i32, release: i32) -> Self {
+        assert!(release <= apply);
+        Hysteresis {
+            apply,
+            release,
+            braking: false,
+        }
+    }
+
+    /// Returns the event if `deceleration` applies or releases the brake.
+    pub fn update(&mut self, deceleration: i32) -> Option<BrakeEvent> {
+        if !self.braking && deceleration >= self.apply {
+            self.braking = true;
+            Some(BrakeEvent::Applied)
+        } else if self.braking && deceleration <= self.release {
+            self.braking = false;
+            Some(BrakeEvent::Released)
+        } else {
+            None
+        }
+    }
+
+    pub fn is_braking(&self) -> bool {
+        self.braking
+    }
+
+    /// Takes over the thresholds of `thresholds`, e.g. when the sensitivity
+    /// is changed. The brake stays applied or released until the next
+    /// [`update`](Self::update) says otherwise.
+    pub fn set_thresholds(&mut self, thresholds: Hysteresis) {
+        self.apply = thresholds.apply;
+        self.release = thresholds.release;
+    }
+}
+
+/// Time constant of the low-pass filter on the samples.
+pub const SMOOTHING: Duration = Duration::from_millis(80);
+
+/// Time constant of the gravity estimate.
+pub const GRAVITY_TIME: Duration = Duration::from_secs(3);
+
+/// Deceleration counting as braking, in milli-g.
+pub const APPLY_MG: i32 = 150;
+
+/// Deceleration below which braking has ended, in milli-g.
+pub const RELEASE_MG: i32 = 70;
+
+// filter states are in 1/2^16 milli-g
+const SHIFT: u32 = 16;
+
+/// Exponential moving average of vectors.
+#[derive(Clone, Copy, Debug)]
+struct LowPass {
+    tau: Duration,
+    state: [i64; 3],
+}
+
+impl LowPass {
+    const fn new(tau: Duration) -> Self {
+        LowPass { tau, state: [0; 3] }
+    }
+
+    fn reset(&mut self, accel: Accel) {
+        self.state = [accel.x, accel.y, accel.z].map(|v| (v as i64) << SHIFT);
+    }
+
+    fn update(&mut self, accel: Accel, dt: Duration) {
+        let dt = dt.as_micros() as i64;
+        let tau = self.tau.as_micros() as i64;
+        let weight = (dt << SHIFT) / (tau + dt).max(1);
+        for (state, v) in self.state.iter_mut().zip([accel.x, accel.y, accel.z]) {
+            *state += ((((v as i64) << SHIFT) - *state) * weight) >> SHIFT;
+        }
+    }
+
+    fn value(&self) -> Accel {
+        let [x, y, z] = self.state.map(|v| (v >> SHIFT) as i32);
+        Accel::new(x, y, z)
+    }
+}
+
+/// Turns acceleration samples into brake events.
+pub struct BrakeDetector {
+    forward: Accel,
+    hysteresis: Hysteresis,
+    smoothed: LowPass,
+    gravity: LowPass,
+    last: Option<Instant>,
+    deceleration: i32,
+}
+
+impl BrakeDetector {
+    /// Detector for a sensor with the axis `forward` pointing ahead, roughly.
+    /// It may be tilted up or down, but not sideways.
+    pub const fn new(forward: Axis) -> Self {
+        BrakeDetector {
+            forward: forward.unit(),
+            hysteresis: Hysteresis::new(APPLY_MG, RELEASE_MG),
+            smoothed: LowPass::new(SMOOTHING),
+            gravity: LowPass::new(GRAVITY_TIME),
+            last: None,
+            deceleration: 0,
+        }
+    }
+
+    /// Time constant of the low-pass filter, [`SMOOTHING`] by default.
+    /// Longer ones ignore more bumps but react later.
+    pub const fn smoothing(mut self, tau: Duration) -> Self {
+        self.smoothed.tau = tau;
+        self
+    }
+
+    /// Time constant of the gravity estimate, [`GRAVITY_TIME`] by default.
+    /// It has to be longer than braking usually takes.
+    pub const fn gravity_time(mut self, tau: Duration) -> Self {
+        self.gravity.tau = tau;
+        self
+    }
+
+    /// Deceleration in milli-g from which on the brake counts as applied, and
+    /// below which it counts as released again, see [`Hysteresis::new`].
+    pub const fn thresholds(mut self, apply_mg: i32, release_mg: i32) -> Self {
+        self.hysteresis = Hysteresis::new(apply_mg, release_mg);
+        self
+    }
+
+    /// Feeds the sample `accel` taken at `now`. Returns the event if the
+    /// brake was just applied or released.
+    ///
+    /// The first sample is taken as the sensor being at rest.
+    pub fn update(&mut self, accel: Accel, now: Instant) -> Option<BrakeEvent> {
+        let Some(last) = self.last.replace(now) else {
+            self.smoothed.reset(accel);
+            self.gravity.reset(accel);
+            return None;
+        };
+        let dt = now - last;
+        self.smoothed.update(accel, dt);
+        if !self.hysteresis.is_braking() {
+            self.gravity.update(accel, dt);
+        }
+        self.deceleration = -self.longitudinal();
+        self.hysteresis.update(self.deceleration)
+    }
+
+    /// Filtered deceleration in milli-g, negative while speeding up.
+    pub fn deceleration(&self) -> i32 {
+        self.deceleration
+    }
+
+    pub fn is_braking(&self) -> bool {
+        self.hysteresis.is_braking()
+    }
+
+    /// Changes the thresholds while running, see [`Hysteresis::set_thresholds`].
+    pub fn set_thresholds(&mut self, thresholds: Hysteresis) {
+        self.hysteresis.set_thresholds(thresholds);
+    }
+
+    /// Acceleration in the direction the bike is heading, horizontally.
+    fn longitudinal(&self) -> i32 {
+        let gravity = self.gravity.value();
+        let smoothed = self.smoothed.value();
+        let motion = Accel::new(
+            smoothed.x - gravity.x,
+            smoothed.y - gravity.y,
+            smoothed.z - gravity.z,
+        );
+
+        // the forward axis without its vertical part, scaled by |gravity|²
+        let g2 = gravity.dot(gravity);
+        let fg = self.forward.dot(gravity);
+        let heading = [
+            (self.forward.x as i64 * g2 - fg * gravity.x as i64),
+            (self.forward.y as i64 * g2 - fg * gravity.y as i64),
+            (self.forward.z as i64 * g2 - fg * gravity.z as i64),
+        ];
+        let length = heading.iter().map(|h| h * h).sum::<i64>() as u64;
+        let length = length.isqrt() as i64;
+        if length == 0 {
+            return 0;
+        }
+        let along = motion.x as i64 * heading[0]
+            + motion.y as i64 * heading[1]
+            + motion.z as i64 * heading[2];
+        (along / length) as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: Duration = Duration::from_millis(10);
+
+    /// Acceleration of the bike, in the sensor frame of a sensor pitched by
+    /// `pitch` degrees with X pointing ahead, plus some road noise.
+    fn sample(forward_mg: f64, pitch: f64, noise: &mut u32) -> Accel {
+        // specific force: gravity reads as 1 g up
+        let (x, z) = (forward_mg, 1000.0);
+        let (sin, cos) = pitch.to_radians().sin_cos();
+        *noise ^= *noise << 13;
+        *noise ^= *noise >> 17;
+        *noise ^= *noise << 5;
+        let n = (*noise % 161) as f64 - 80.0;
+        Accel::new(
+            (x * cos - z * sin + n) as i32,
+            (n / 2.0) as i32,
+            (x * sin + z * cos - n) as i32,
+        )
+    }
+
+    /// Runs a trace of forward accelerations, one per `RATE`, and returns
+    /// the events with their sample index.
+    fn detect(detector: &mut BrakeDetector, trace: &[f64], pitch: f64) -> Vec<(usize, BrakeEvent)> {
+        let mut noise = 0x1234_5678;
+        let mut events = Vec::new();
+        for (i, &forward) in trace.iter().enumerate() {
+            let now = Instant::from_micros(i as u64 * RATE.as_micros() as u64);
+            if let Some(event) = detector.update(sample(forward, pitch, &mut noise), now) {
+                events.push((i, event));
+            }
+        }
+        events
+    }
+
+    /// Cruising for 2 s, braking at `decel_mg` for 2 s, then cruising again.
+    fn stop(decel_mg: f64) -> Vec<f64> {
+        let mut trace = vec![0.0; 200];
+        trace.extend([-decel_mg; 200]);
+        trace.extend([0.0; 300]);
+        trace
+    }
+
+    #[test]
+    fn detects_braking_once() {
+        let mut detector = BrakeDetector::new(Axis::X);
+        let events = detect(&mut detector, &stop(300.0), 0.0);
+        assert_eq!(events.len(), 2, "{:?}", events);
+        let (applied, released) = (events[0], events[1]);
+        assert_eq!(applied.1, BrakeEvent::Applied);
+        assert_eq!(released.1, BrakeEvent::Released);
+        // within 100 ms, the smoothing delays both ends the same
+        assert!((200..210).contains(&applied.0), "{:?}", events);
+        assert!((400..420).contains(&released.0), "{:?}", events);
+        assert!(!detector.is_braking());
+    }
+
+    #[test]
+    fn noise_and_light_braking_are_ignored() {
+        // the last one is speeding up
+        for trace in [vec![0.0; 1000], stop(50.0), stop(-300.0)] {
+            let mut detector = BrakeDetector::new(Axis::X);
+            assert_eq!(detect(&mut detector, &trace, 0.0), []);
+        }
+    }
+
+    #[test]
+    fn tilted_sensor_is_compensated() {
+        for pitch in [-30.0, 15.0, 40.0] {
+            let mut detector = BrakeDetector::new(Axis::X);
+            assert_eq!(detect(&mut detector, &[0.0; 500], pitch), []);
+            assert!(detector.deceleration().abs() < 20, "{}", pitch);
+
+            let mut detector = BrakeDetector::new(Axis::X);
+            let events = detect(&mut detector, &stop(300.0), pitch);
+            assert_eq!(events.len(), 2, "{} {:?}", pitch, events);
+        }
+    }
+
+    #[test]
+    fn hysteresis_keeps_the_brake_on() {
+        // hovering around the apply threshold after applying the brake
+        let mut trace = vec![0.0; 200];
+        trace.extend([-300.0; 50]);
+        for i in 0..200 {
+            trace.push(if i % 20 < 10 { -170.0 } else { -110.0 });
+        }
+        let mut detector = BrakeDetector::new(Axis::X).thresholds(150, 70);
+        let events = detect(&mut detector, &trace, 0.0);
+        assert_eq!(events.len(), 1, "{:?}", events);
+        assert!(detector.is_braking());
+    }
+
+    #[test]
+    fn changing_thresholds_keeps_the_state() {
+        let mut hysteresis = Hysteresis::new(150, 70);
+        assert_eq!(hysteresis.update(200), Some(BrakeEvent::Applied));
+
+        hysteresis.set_thresholds(Hysteresis::new(250, 120));
+        assert!(hysteresis.is_braking());
+        assert_eq!(hysteresis.update(200), None);
+        assert_eq!(hysteresis.update(100), Some(BrakeEvent::Released));
+
+        hysteresis.set_thresholds(Hysteresis::new(100, 50));
+        assert!(!hysteresis.is_braking());
+        assert_eq!(hysteresis.update(100), Some(BrakeEvent::Applied));
+    }
+}
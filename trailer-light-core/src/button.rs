@@ -0,0 +1,223 @@
+//! Gestures of a momentary push button.
+//!
+//! [`Button`] is fed the level of the button with a timestamp, on every
+//! edge and regularly in between, e.g. once per frame, and recognizes
+//! [`Gesture`]s. Some gestures are only certain after a timeout, a short
+//! press for example once no second press follows, so it has to see the
+//! time passing even while nothing changes.
+
+use core::time::Duration;
+
+use crate::time::Instant;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Gesture {
+    /// Pressed and released, with no second press following.
+    Short,
+    /// Held for [`LONG_PRESS`]. Reported while still held.
+    Long,
+    /// Pressed twice quickly.
+    Double,
+    /// Already pressed at boot and held for [`BOOT_HOLD`].
+    HeldAtBoot,
+}
+
+/// Edges closer than this to the last change are bounces.
+pub const DEBOUNCE: Duration = Duration::from_millis(20);
+
+/// How long a press has to be held to be long.
+pub const LONG_PRESS: Duration = Duration::from_millis(800);
+
+/// Time the second press of a double press may follow the release of the
+/// first one.
+pub const DOUBLE_PRESS: Duration = Duration::from_millis(300);
+
+/// How long the button has to be held at boot.
+pub const BOOT_HOLD: Duration = Duration::from_secs(2);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    /// Before the first update.
+    Boot,
+    /// Pressed since boot.
+    BootPress(Instant),
+    Idle,
+    /// Pressed, the second press of a double press if `second`.
+    Pressed {
+        since: Instant,
+        second: bool,
+    },
+    /// Released after a press, waiting for a second one.
+    Released(Instant),
+    /// A gesture has been reported, waiting for the release.
+    Done,
+}
+
+/// Recognizes the gestures of a button.
+#[derive(Clone, Debug)]
+pub struct Button {
+    state: State,
+    pressed: bool,
+    changed: Option<Instant>,
+}
+
+impl Button {
+    pub const fn new() -> Self {
+        Button {
+            state: State::Boot,
+            pressed: false,
+            changed: None,
+        }
+    }
+
+    /// Feeds the level of the button at `now`, `pressed` being `true` while
+    /// it's held down. Returns the gesture it completes, if any.
+    ///
+    /// The first update is taken as the level at boot.
+    pub fn update(&mut self, pressed: bool, now: Instant) -> Option<Gesture> {
+        if pressed != self.pressed {
+            if self.changed.is_some_and(|changed| now - changed < DEBOUNCE) {
+                // a bounce, keep the last level
+                return self.tick(self.pressed, now);
+            }
+            self.pressed = pressed;
+            self.changed = Some(now);
+        }
+        self.tick(pressed, now)
+    }
+
+    fn tick(&mut self, pressed: bool, now: Instant) -> Option<Gesture> {
+        let (state, gesture) = match (self.state, pressed) {
+            (State::Boot, true) => (State::BootPress(now), None),
+            (State::Boot, false) => (State::Idle, None),
+            (State::BootPress(since), true) if now - since >= BOOT_HOLD => {
+                (State::Done, Some(Gesture::HeldAtBoot))
+            }
+            (State::BootPress(_), true) => return None,
+            // released too early, not a gesture
+            (State::BootPress(_), false) => (State::Idle, None),
+            (State::Idle, true) => (
+                State::Pressed {
+                    since: now,
+                    second: false,
+                },
+                None,
+            ),
+            (State::Idle, false) => return None,
+            (State::Pressed { since, .. }, true) if now - since >= LONG_PRESS => {
+                (State::Done, Some(Gesture::Long))
+            }
+            (State::Pressed { .. }, true) => return None,
+            (State::Pressed { second: true, .. }, false) => (State::Idle, Some(Gesture::Double)),
+            (State::Pressed { second: false, .. }, false) => (State::Released(now), None),
+            (State::Released(_), true) => (
+                State::Pressed {
+                    since: now,
+                    second: true,
+                },
+                None,
+            ),
+            (State::Released(at), false) if now - at > DOUBLE_PRESS => {
+                (State::Idle, Some(Gesture::Short))
+            }
+            (State::Released(_), false) => return None,
+            (State::Done, true) => return None,
+            (State::Done, false) => (State::Idle, None),
+        };
+        self.state = state;
+        gesture
+    }
+
+    /// Whether the button is held down, debounced.
+    pub fn is_pressed(&self) -> bool {
+        self.pressed
+    }
+}
+
+impl Default for Button {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Plays `levels`, `(ms, pressed)` with the level holding until the next
+    /// entry, and updates every millisecond until 1 s after the last one.
+    /// Returns the gestures with the time they were recognized.
+    fn gestures(levels: &[(u64, bool)]) -> Vec<(u64, Gesture)> {
+        let mut button = Button::new();
+        let end = levels.last().unwrap().0 + 1_000;
+        let mut gestures = Vec::new();
+        for ms in 0..end {
+            let pressed = levels
+                .iter()
+                .rev()
+                .find(|(t, _)| *t <= ms)
+                .is_some_and(|(_, pressed)| *pressed);
+            if let Some(gesture) = button.update(pressed, Instant::from_micros(ms * 1_000)) {
+                gestures.push((ms, gesture));
+            }
+        }
+        gestures
+    }
+
+    #[test]
+    fn short_press_once_no_second_follows() {
+        assert_eq!(
+            gestures(&[(100, true), (200, false)]),
+            [(501, Gesture::Short)]
+        );
+    }
+
+    #[test]
+    fn long_press_while_held() {
+        assert_eq!(
+            gestures(&[(100, true), (2_000, false)]),
+            [(900, Gesture::Long)]
+        );
+    }
+
+    #[test]
+    fn double_press() {
+        assert_eq!(
+            gestures(&[(100, true), (200, false), (400, true), (500, false)]),
+            [(500, Gesture::Double)]
+        );
+        // too slow for a double press
+        assert_eq!(
+            gestures(&[(100, true), (200, false), (600, true), (700, false)]),
+            [(501, Gesture::Short), (1_001, Gesture::Short)]
+        );
+    }
+
+    #[test]
+    fn bounces_are_filtered() {
+        let bouncy = [
+            (100, true),
+            (102, false),
+            (105, true),
+            (200, false),
+            (203, true),
+            (210, false),
+        ];
+        assert_eq!(gestures(&bouncy), [(501, Gesture::Short)]);
+    }
+
+    #[test]
+    fn held_at_boot() {
+        assert_eq!(
+            gestures(&[(0, true), (3_000, false)]),
+            [(2_000, Gesture::HeldAtBoot)]
+        );
+        // released before, nothing happens
+        assert_eq!(gestures(&[(0, true), (1_000, false)]), []);
+        // pressed after boot, a long press
+        assert_eq!(
+            gestures(&[(0, false), (10, true), (3_000, false)]),
+            [(810, Gesture::Long)]
+        );
+    }
+}
@@ -0,0 +1,329 @@
+//! Color spaces, interpolation and palettes.
+//!
+//! [`Hsv`] and [`Hsl`] use 16 bit channels like the frame buffer, the hue
+//! being a fraction of a full turn, so it wraps around like the color wheel.
+//! [`hue`] converts from degrees.
+
+use crate::{widen, Color, Color16};
+
+const MAX: u32 = u16::MAX as u32;
+
+/// Hue of `degrees` on the color wheel.
+pub const fn hue(degrees: u16) -> u16 {
+    ((degrees as u32 % 360) * 65_536 / 360) as u16
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Hsv {
+    pub h: u16,
+    pub s: u16,
+    pub v: u16,
+}
+
+impl Hsv {
+    pub const fn new(h: u16, s: u16, v: u16) -> Self {
+        Hsv { h, s, v }
+    }
+}
+
+impl From<Hsv> for Color16 {
+    fn from(Hsv { h, s, v }: Hsv) -> Self {
+        let (s, v) = (s as u32, v as u32);
+        let sector = h as u32 * 6;
+        let rem = sector & 0xffff;
+        let p = v * (MAX - s) / MAX;
+        let q = v * (MAX - s * rem / MAX) / MAX;
+        let t = v * (MAX - s * (MAX - rem) / MAX) / MAX;
+        let (r, g, b) = match sector >> 16 {
+            0 => (v, t, p),
+            1 => (q, v, p),
+            2 => (p, v, t),
+            3 => (p, q, v),
+            4 => (t, p, v),
+            _ => (v, p, q),
+        };
+        Color16::new(r as u16, g as u16, b as u16)
+    }
+}
+
+impl From<Color16> for Hsv {
+    fn from(c: Color16) -> Self {
+        let (r, g, b) = (c.r as i64, c.g as i64, c.b as i64);
+        let max = r.max(g).max(b);
+        let delta = max - r.min(g).min(b);
+        if delta == 0 {
+            return Hsv::new(0, 0, max as u16);
+        }
+        let (offset, diff) = if max == r {
+            (0, g - b)
+        } else if max == g {
+            (65_536 / 3, b - r)
+        } else {
+            (2 * 65_536 / 3, r - g)
+        };
+        let h = (offset + diff * 65_536 / (6 * delta)).rem_euclid(65_536);
+        Hsv::new(h as u16, (delta * MAX as i64 / max) as u16, max as u16)
+    }
+}
+
+impl From<Hsv> for Color {
+    fn from(hsv: Hsv) -> Self {
+        let c = Color16::from(hsv);
+        Color::new((c.r >> 8) as u8, (c.g >> 8) as u8, (c.b >> 8) as u8)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Hsl {
+    pub h: u16,
+    pub s: u16,
+    pub l: u16,
+}
+
+impl Hsl {
+    pub const fn new(h: u16, s: u16, l: u16) -> Self {
+        Hsl { h, s, l }
+    }
+}
+
+impl From<Hsl> for Hsv {
+    fn from(Hsl { h, s, l }: Hsl) -> Self {
+        let l = l as u32;
+        let v = l + s as u32 * l.min(MAX - l) / MAX;
+        let s = match v {
+            0 => 0,
+            v => 2 * (v - l) * MAX / v,
+        };
+        Hsv::new(h, s.min(MAX) as u16, v as u16)
+    }
+}
+
+impl From<Hsv> for Hsl {
+    fn from(Hsv { h, s, v }: Hsv) -> Self {
+        let v = v as u32;
+        let l = v - v * s as u32 / (2 * MAX);
+        let s = match l.min(MAX - l) {
+            0 => 0,
+            m => (v - l) * MAX / m,
+        };
+        Hsl::new(h, s.min(MAX) as u16, l as u16)
+    }
+}
+
+impl From<Hsl> for Color16 {
+    fn from(hsl: Hsl) -> Self {
+        Hsv::from(hsl).into()
+    }
+}
+
+impl From<Color16> for Hsl {
+    fn from(c: Color16) -> Self {
+        Hsv::from(c).into()
+    }
+}
+
+/// `color` at the 16 bit brightness `v`.
+pub fn scale(color: Color, v: u16) -> Color16 {
+    let channel = |c: u8| (v as u32 * c as u32 / 255) as u16;
+    Color16::new(channel(color.r), channel(color.g), channel(color.b))
+}
+
+/// How colors in between two others are calculated.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Interpolation {
+    /// Straight through RGB, red to green passes a dim yellow.
+    #[default]
+    Rgb,
+    /// Around the shorter way of the color wheel, red to green passes a
+    /// bright yellow.
+    Hsv,
+}
+
+impl Interpolation {
+    /// The color `weight` of the way from `from` to `to`, `u16::MAX` being
+    /// `to`.
+    pub fn lerp(self, from: Color16, to: Color16, weight: u16) -> Color16 {
+        let lerp =
+            |a: u16, b: u16| (a as i64 + (b as i64 - a as i64) * weight as i64 / MAX as i64) as u16;
+        match self {
+            _ if weight == 0 => from,
+            _ if weight == u16::MAX => to,
+            Interpolation::Rgb => {
+                Color16::new(lerp(from.r, to.r), lerp(from.g, to.g), lerp(from.b, to.b))
+            }
+            Interpolation::Hsv => {
+                let (mut a, mut b) = (Hsv::from(from), Hsv::from(to));
+                // grays have no hue, keep the one of the other color
+                if a.s == 0 {
+                    a.h = b.h;
+                }
+                if b.s == 0 {
+                    b.h = a.h;
+                }
+                let dh = b.h.wrapping_sub(a.h) as i16 as i64;
+                let h = (a.h as i64 + dh * weight as i64 / MAX as i64) as u16;
+                Hsv::new(h, lerp(a.s, b.s), lerp(a.v, b.v)).into()
+            }
+        }
+    }
+}
+
+/// Colors evenly spread from 0 to `u16::MAX`, with the ones in between
+/// interpolated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Palette<'a> {
+    stops: &'a [Color],
+    interpolation: Interpolation,
+}
+
+impl<'a> Palette<'a> {
+    /// # Panics
+    ///
+    /// If `stops` is empty.
+    pub const fn new(stops: &'a [Color], interpolation: Interpolation) -> Self {
+        assert!(!stops.is_empty());
+        Palette {
+            stops,
+            interpolation,
+        }
+    }
+
+    /// The color at `x`, 0 being the first stop and `u16::MAX` the last.
+    pub fn at(&self, x: u16) -> Color16 {
+        let last = self.stops.len() - 1;
+        let pos = x as u64 * last as u64 * 65_536 / MAX as u64;
+        let i = (pos >> 16) as usize;
+        let from = widen(self.stops[i.min(last)]);
+        if i >= last {
+            return from;
+        }
+        let to = widen(self.stops[i + 1]);
+        self.interpolation.lerp(from, to, pos as u16)
+    }
+}
+
+/// Black to full red.
+pub const RED: Palette = Palette::new(
+    &[Color::new(0, 0, 0), Color::new(255, 0, 0)],
+    Interpolation::Rgb,
+);
+
+/// Black to amber, the color of turn indicators.
+pub const AMBER: Palette = Palette::new(
+    &[Color::new(0, 0, 0), crate::indicator::AMBER],
+    Interpolation::Rgb,
+);
+
+/// Black through red and yellow to white, like glowing metal.
+pub const FIRE: Palette = Palette::new(
+    &[
+        Color::new(0, 0, 0),
+        Color::new(255, 0, 0),
+        Color::new(255, 200, 0),
+        Color::new(255, 255, 255),
+    ],
+    Interpolation::Rgb,
+);
+
+/// Colors of a sweep, see [`AnimationContext::render_colors`](crate::AnimationContext::render_colors).
+///
+/// The highlight fades into the base or target color over its width.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SweepColors {
+    /// LEDs the highlight hasn't reached yet.
+    pub base: Color,
+    /// LEDs the highlight has passed.
+    pub target: Color,
+    pub highlight: Color,
+    pub interpolation: Interpolation,
+}
+
+impl SweepColors {
+    /// Color of an LED, given whether the highlight has passed it and how
+    /// much the highlight lights it.
+    pub fn at(&self, passed: bool, highlight: u16) -> Color16 {
+        let ambient = widen(if passed { self.target } else { self.base });
+        self.interpolation
+            .lerp(ambient, widen(self.highlight), highlight)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED16: Color16 = Color16::new(u16::MAX, 0, 0);
+    const GREEN16: Color16 = Color16::new(0, u16::MAX, 0);
+
+    #[test]
+    fn hsv_round_trips() {
+        for c in [
+            Color16::new(0, 0, 0),
+            Color16::new(u16::MAX, u16::MAX, u16::MAX),
+            RED16,
+            Color16::new(12_000, 40_000, 3_000),
+            Color16::new(500, 200, 65_000),
+            Color16::new(30_000, 10_000, 29_000),
+        ] {
+            let back = Color16::from(Hsv::from(c));
+            for (a, b) in [(c.r, back.r), (c.g, back.g), (c.b, back.b)] {
+                assert!(a.abs_diff(b) <= 8, "{:?} {:?}", c, back);
+            }
+            let back = Color16::from(Hsl::from(c));
+            for (a, b) in [(c.r, back.r), (c.g, back.g), (c.b, back.b)] {
+                assert!(a.abs_diff(b) <= 8, "{:?} {:?}", c, back);
+            }
+        }
+    }
+
+    #[test]
+    fn primaries() {
+        assert_eq!(
+            Color::from(Hsv::new(0, u16::MAX, u16::MAX)),
+            Color::new(255, 0, 0)
+        );
+        assert_eq!(
+            Color::from(Hsv::new(hue(120), u16::MAX, u16::MAX)),
+            Color::new(0, 255, 0)
+        );
+        assert_eq!(
+            Color::from(Hsv::new(hue(240), u16::MAX, u16::MAX)),
+            Color::new(0, 0, 255)
+        );
+        let red = Hsl::from(RED16);
+        assert_eq!((red.h, red.s), (0, u16::MAX));
+        assert!(red.l.abs_diff(u16::MAX / 2) <= 1);
+    }
+
+    #[test]
+    fn hsv_interpolation_keeps_brightness() {
+        let rgb = Interpolation::Rgb.lerp(RED16, GREEN16, u16::MAX / 2);
+        let hsv = Interpolation::Hsv.lerp(RED16, GREEN16, u16::MAX / 2);
+        assert_eq!(rgb, Color16::new(32_768, 32_767, 0));
+        assert!(hsv.r > 65_000 && hsv.g > 65_000 && hsv.b == 0, "{:?}", hsv);
+
+        for mode in [Interpolation::Rgb, Interpolation::Hsv] {
+            assert_eq!(mode.lerp(RED16, GREEN16, 0), RED16);
+            assert_eq!(mode.lerp(RED16, GREEN16, u16::MAX), GREEN16);
+        }
+    }
+
+    #[test]
+    fn hsv_interpolation_takes_the_short_way() {
+        let magenta = Color16::new(u16::MAX, 0, u16::MAX);
+        let c = Interpolation::Hsv.lerp(magenta, Color16::new(u16::MAX, u16::MAX, 0), u16::MAX / 2);
+        // through red, not through green and blue
+        assert!(c.r > 65_000 && c.g < 100 && c.b < 100, "{:?}", c);
+    }
+
+    #[test]
+    fn palette_hits_its_stops() {
+        assert_eq!(FIRE.at(0), Color16::new(0, 0, 0));
+        assert_eq!(FIRE.at(u16::MAX / 3), RED16);
+        assert_eq!(
+            FIRE.at(u16::MAX),
+            Color16::new(u16::MAX, u16::MAX, u16::MAX)
+        );
+        assert_eq!(RED.at(1000), Color16::new(1000, 0, 0));
+    }
+}
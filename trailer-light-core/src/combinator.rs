@@ -0,0 +1,367 @@
+//! Building effects out of other effects.
+//!
+//! [`EffectExt`] works like [`Iterator`]'s adapters, only on effects: sweeps
+//! can be played one after another with [`chain`](EffectExt::chain), blended
+//! with [`blend`](EffectExt::blend), recolored with [`map`](EffectExt::map)
+//! and so on, each adapter being an effect itself. [`frames`](EffectExt::frames)
+//! turns an effect into an actual iterator of frames.
+
+use core::time::Duration;
+
+use crate::{
+    compositor::BlendMode,
+    effect::{Effect, Frame},
+    Color16, NUM_LEDS,
+};
+
+pub trait EffectExt: Effect + Sized {
+    /// Plays `next` once this effect has finished. Only effects that know
+    /// their [`duration`](Effect::duration) can be followed, otherwise `next`
+    /// is never played.
+    fn chain<N: Effect>(self, next: N) -> Chain<Self, N> {
+        Chain {
+            first: self,
+            second: next,
+        }
+    }
+
+    /// Plays both effects at the same time, combining their frames with
+    /// `mode`, the other effect on top. Runs until both have finished.
+    fn blend<O: Effect>(self, other: O, mode: BlendMode) -> Blend<Self, O> {
+        Blend {
+            a: self,
+            b: other,
+            mode,
+        }
+    }
+
+    /// Transforms the color of every LED with `f`.
+    fn map<F: FnMut(Color16) -> Color16>(self, f: F) -> Map<Self, F> {
+        Map { effect: self, f }
+    }
+
+    /// Ends the effect after `duration`, or earlier if it finishes before.
+    fn take_for(self, duration: Duration) -> TakeFor<Self> {
+        TakeFor {
+            effect: self,
+            duration,
+        }
+    }
+
+    /// Plays the effect backwards.
+    ///
+    /// # Panics
+    ///
+    /// If the effect doesn't know its [`duration`](Effect::duration).
+    fn reverse(self) -> Reverse<Self> {
+        let duration = self
+            .duration()
+            .expect("only effects with a duration can be reversed");
+        Reverse {
+            effect: self,
+            duration,
+        }
+    }
+
+    /// Plays the effect again whenever it finishes, `times` times in total,
+    /// 0 repeats it forever.
+    fn repeat(self, times: u16) -> Repeat<Self> {
+        Repeat {
+            effect: self,
+            times,
+            plays: 0,
+            offset: Duration::ZERO,
+        }
+    }
+
+    /// Iterator over the frames of the effect, `interval` apart, up to and
+    /// including its last one.
+    fn frames(self, interval: Duration) -> Frames<Self> {
+        Frames {
+            effect: self,
+            interval,
+            t: Some(Duration::ZERO),
+        }
+    }
+}
+
+impl<E: Effect> EffectExt for E {}
+
+/// See [`EffectExt::chain`].
+pub struct Chain<A, B> {
+    first: A,
+    second: B,
+}
+
+impl<A: Effect, B: Effect> Effect for Chain<A, B> {
+    fn render(&mut self, t: Duration, frame: &mut Frame) -> bool {
+        // from the duration rather than when `first` was seen finishing, so
+        // the time may also go backwards
+        match self.first.duration() {
+            Some(offset) if t >= offset => self.second.render(t - offset, frame),
+            Some(_) => {
+                self.first.render(t, frame);
+                true
+            }
+            None => self.first.render(t, frame),
+        }
+    }
+
+    fn duration(&self) -> Option<Duration> {
+        Some(self.first.duration()? + self.second.duration()?)
+    }
+}
+
+/// See [`EffectExt::blend`].
+pub struct Blend<A, B> {
+    a: A,
+    b: B,
+    mode: BlendMode,
+}
+
+impl<A: Effect, B: Effect> Effect for Blend<A, B> {
+    fn render(&mut self, t: Duration, frame: &mut Frame) -> bool {
+        let mut other = [Color16::new(0, 0, 0); NUM_LEDS];
+        let running = self.a.render(t, frame) | self.b.render(t, &mut other);
+        for (c, o) in frame.iter_mut().zip(other) {
+            *c = Color16::new(
+                self.mode.blend(c.r, o.r),
+                self.mode.blend(c.g, o.g),
+                self.mode.blend(c.b, o.b),
+            );
+        }
+        running
+    }
+
+    fn duration(&self) -> Option<Duration> {
+        Some(self.a.duration()?.max(self.b.duration()?))
+    }
+}
+
+/// See [`EffectExt::map`].
+pub struct Map<E, F> {
+    effect: E,
+    f: F,
+}
+
+impl<E: Effect, F: FnMut(Color16) -> Color16> Effect for Map<E, F> {
+    fn render(&mut self, t: Duration, frame: &mut Frame) -> bool {
+        let running = self.effect.render(t, frame);
+        for c in frame.iter_mut() {
+            *c = (self.f)(*c);
+        }
+        running
+    }
+
+    fn duration(&self) -> Option<Duration> {
+        self.effect.duration()
+    }
+}
+
+/// See [`EffectExt::take_for`].
+pub struct TakeFor<E> {
+    effect: E,
+    duration: Duration,
+}
+
+impl<E: Effect> Effect for TakeFor<E> {
+    fn render(&mut self, t: Duration, frame: &mut Frame) -> bool {
+        self.effect.render(t.min(self.duration), frame) && t < self.duration
+    }
+
+    fn duration(&self) -> Option<Duration> {
+        Some(match self.effect.duration() {
+            Some(d) => d.min(self.duration),
+            None => self.duration,
+        })
+    }
+}
+
+/// See [`EffectExt::reverse`].
+pub struct Reverse<E> {
+    effect: E,
+    duration: Duration,
+}
+
+impl<E: Effect> Effect for Reverse<E> {
+    fn render(&mut self, t: Duration, frame: &mut Frame) -> bool {
+        self.effect.render(self.duration.saturating_sub(t), frame);
+        t < self.duration
+    }
+
+    fn duration(&self) -> Option<Duration> {
+        Some(self.duration)
+    }
+}
+
+/// See [`EffectExt::repeat`].
+pub struct Repeat<E> {
+    effect: E,
+    times: u16,
+    plays: u16,
+    // start of the current play
+    offset: Duration,
+}
+
+impl<E: Effect> Effect for Repeat<E> {
+    fn render(&mut self, t: Duration, frame: &mut Frame) -> bool {
+        loop {
+            let local = t.saturating_sub(self.offset);
+            if self.effect.render(local, frame) {
+                return true;
+            }
+            self.plays = self.plays.saturating_add(1);
+            if self.times != 0 && self.plays >= self.times {
+                return false;
+            }
+            match self.effect.duration() {
+                // start the next play where this one ended, so repeating
+                // doesn't drift, unless it ended as soon as it started
+                Some(d) if !d.is_zero() && !local.is_zero() => self.offset += d,
+                _ => {
+                    self.offset = t;
+                    return true;
+                }
+            }
+        }
+    }
+
+    fn duration(&self) -> Option<Duration> {
+        match self.times {
+            0 => None,
+            n => Some(self.effect.duration()? * n.into()),
+        }
+    }
+}
+
+/// See [`EffectExt::frames`].
+pub struct Frames<E> {
+    effect: E,
+    interval: Duration,
+    // time of the next frame, `None` after the last one
+    t: Option<Duration>,
+}
+
+impl<E: Effect> Iterator for Frames<E> {
+    type Item = Frame;
+
+    fn next(&mut self) -> Option<Frame> {
+        let t = self.t?;
+        let mut frame = [Color16::new(0, 0, 0); NUM_LEDS];
+        self.t = match self.effect.render(t, &mut frame) {
+            true => Some(t + self.interval),
+            false => None,
+        };
+        Some(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Brightness of the first LED is the time in ms, finishes after `len` ms.
+    struct Ramp {
+        len: u64,
+    }
+
+    impl Effect for Ramp {
+        fn render(&mut self, t: Duration, frame: &mut Frame) -> bool {
+            let ms = (t.as_millis() as u64).min(self.len);
+            *frame = [Color16::new(ms as u16, 0, 0); NUM_LEDS];
+            ms < self.len
+        }
+
+        fn duration(&self) -> Option<Duration> {
+            Some(Duration::from_millis(self.len))
+        }
+    }
+
+    fn ramp(len: u64) -> Ramp {
+        Ramp { len }
+    }
+
+    /// First LED of every frame, 1 ms apart.
+    fn values(effect: impl Effect) -> Vec<u16> {
+        effect
+            .frames(Duration::from_millis(1))
+            .map(|frame| frame[0].r)
+            .collect()
+    }
+
+    #[test]
+    fn frames_end_with_the_last_one() {
+        assert_eq!(values(ramp(3)), [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn chain_plays_one_after_the_other() {
+        assert_eq!(values(ramp(2).chain(ramp(3))), [0, 1, 0, 1, 2, 3]);
+        assert_eq!(
+            ramp(2).chain(ramp(3)).duration(),
+            Some(Duration::from_millis(5))
+        );
+    }
+
+    #[test]
+    fn chain_can_be_reversed() {
+        assert_eq!(values(ramp(2).chain(ramp(3)).reverse()), [3, 2, 1, 0, 1, 0]);
+    }
+
+    #[test]
+    fn blend_runs_until_both_have_finished() {
+        let tripled = ramp(2).map(|c| Color16::new(c.r * 3, 0, 0));
+        assert_eq!(
+            values(ramp(4).blend(tripled, BlendMode::Max)),
+            [0, 3, 6, 6, 6]
+        );
+        assert_eq!(values(ramp(2).blend(ramp(3), BlendMode::Add)), [0, 2, 4, 5]);
+    }
+
+    #[test]
+    fn take_for_cuts_effects_short() {
+        assert_eq!(
+            values(ramp(5).take_for(Duration::from_millis(2))),
+            [0, 1, 2]
+        );
+        assert_eq!(values(ramp(1).take_for(Duration::from_millis(3))), [0, 1]);
+    }
+
+    #[test]
+    fn reverse_plays_backwards() {
+        assert_eq!(values(ramp(3).reverse()), [3, 2, 1, 0]);
+    }
+
+    #[test]
+    fn repeat_restarts_without_drift() {
+        assert_eq!(values(ramp(2).repeat(3)), [0, 1, 0, 1, 0, 1, 2]);
+        assert_eq!(ramp(2).repeat(3).duration(), Some(Duration::from_millis(6)));
+
+        let mut forever = ramp(2).repeat(0);
+        let mut frame = [Color16::new(0, 0, 0); NUM_LEDS];
+        assert!(forever.render(Duration::from_millis(1001), &mut frame));
+        assert_eq!(frame[0].r, 1);
+        assert_eq!(forever.duration(), None);
+    }
+
+    /// Claims to take 10 ms, but is finished right away.
+    struct EndsAtOnce;
+
+    impl Effect for EndsAtOnce {
+        fn render(&mut self, _t: Duration, _frame: &mut Frame) -> bool {
+            false
+        }
+
+        fn duration(&self) -> Option<Duration> {
+            Some(Duration::from_millis(10))
+        }
+    }
+
+    #[test]
+    fn repeating_an_effect_that_ends_at_once_returns() {
+        let mut forever = EndsAtOnce.repeat(0);
+        let mut frame = [Color16::new(0, 0, 0); NUM_LEDS];
+        assert!(forever.render(Duration::ZERO, &mut frame));
+        assert!(forever.render(Duration::from_millis(25), &mut frame));
+    }
+}
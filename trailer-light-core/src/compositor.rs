@@ -0,0 +1,389 @@
+//! Stacking several effects on top of each other.
+//!
+//! Every layer renders its effect into a frame of its own, which is then
+//! blended onto the layers below, lowest priority first. This is the same
+//! idea as the max of highlight and ambient in
+//! [`AnimationContext::calc_value`](crate::AnimationContext::calc_value),
+//! applied to whole frames: a brake flash can sit on top of the wave without
+//! either effect knowing about the other.
+
+use core::ops::Range;
+
+use crate::{
+    effect::{Effect, Frame, Player},
+    time::Instant,
+    Color16, NUM_LEDS,
+};
+
+/// How a layer is combined with the layers below it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlendMode {
+    /// The brighter of both, per channel.
+    Max,
+    /// The sum of both, saturating at full brightness.
+    Add,
+    /// The layer covers what's below, see through to the extent of its
+    /// opacity.
+    Alpha,
+    /// The layer below, dimmed by the layer. Useful for masks and fades.
+    Multiply,
+}
+
+impl BlendMode {
+    /// Blends one channel of the layer onto the channel below it.
+    pub fn blend(self, below: u16, layer: u16) -> u16 {
+        match self {
+            BlendMode::Max => below.max(layer),
+            BlendMode::Add => below.saturating_add(layer),
+            BlendMode::Alpha => layer,
+            BlendMode::Multiply => (below as u32 * layer as u32 / u16::MAX as u32) as u16,
+        }
+    }
+}
+
+/// Fully opaque, see [`Layer::opacity`].
+pub const OPAQUE: u8 = u8::MAX;
+
+/// How a layer is stacked, see [`Compositor::add`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Layer {
+    blend: BlendMode,
+    priority: i8,
+    opacity: u8,
+    leds: Range<usize>,
+}
+
+impl Layer {
+    /// Opaque layer of priority 0 covering the whole strip.
+    pub const fn new(blend: BlendMode) -> Self {
+        Layer {
+            blend,
+            priority: 0,
+            opacity: OPAQUE,
+            leds: 0..NUM_LEDS,
+        }
+    }
+
+    /// Layers with higher priority are blended on top of lower ones, layers
+    /// of the same priority in the order they were added.
+    pub const fn priority(mut self, priority: i8) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// How much the blended layer replaces what's below, [`OPAQUE`] fully
+    /// replaces it, 0 makes the layer invisible.
+    pub const fn opacity(mut self, opacity: u8) -> Self {
+        self.opacity = opacity;
+        self
+    }
+
+    /// Limits the layer to some of the LEDs, the others are left as they are.
+    /// LEDs past the end of the strip are ignored, a range ending before it
+    /// starts covers none.
+    pub const fn leds(mut self, leds: Range<usize>) -> Self {
+        self.leds = leds;
+        self
+    }
+
+    fn blend(&self, below: &mut Color16, layer: Color16) {
+        let channel = |below: u16, layer: u16| {
+            let blended = self.blend.blend(below, layer) as i32;
+            let below = below as i32;
+            (below + (blended - below) * self.opacity as i32 / OPAQUE as i32) as u16
+        };
+        *below = Color16::new(
+            channel(below.r, layer.r),
+            channel(below.g, layer.g),
+            channel(below.b, layer.b),
+        );
+    }
+}
+
+/// Handle of a layer of a [`Compositor`]. It stays tied to its layer, also
+/// after the slot is reused by a layer added later.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LayerId {
+    slot: usize,
+    seq: u32,
+}
+
+struct Slot<'a> {
+    effect: &'a mut dyn Effect,
+    start: Instant,
+    layer: Layer,
+    // keeps layers of the same priority in the order they were added, and
+    // tells them apart from earlier layers in the same slot
+    seq: u32,
+}
+
+/// Stack of up to `N` effect layers.
+///
+/// Like the [`Runner`](crate::effect::Runner), a layer is dropped once its
+/// effect has finished.
+pub struct Compositor<'a, const N: usize> {
+    slots: [Option<Slot<'a>>; N],
+    next_seq: u32,
+}
+
+impl<'a, const N: usize> Compositor<'a, N> {
+    pub fn new() -> Self {
+        Compositor {
+            slots: [(); N].map(|_| None),
+            next_seq: 0,
+        }
+    }
+
+    /// Starts `effect` at `now` as a new layer. Returns `None` if all `N`
+    /// layers are in use.
+    pub fn add(
+        &mut self,
+        effect: &'a mut dyn Effect,
+        now: Instant,
+        mut layer: Layer,
+    ) -> Option<LayerId> {
+        let slot = self.slots.iter().position(Option::is_none)?;
+        let end = layer.leds.end.min(NUM_LEDS);
+        layer.leds = layer.leds.start.min(end)..end;
+        let seq = self.next_seq;
+        self.slots[slot] = Some(Slot {
+            effect,
+            start: now,
+            layer,
+            seq,
+        });
+        self.next_seq = self.next_seq.wrapping_add(1);
+        Some(LayerId { slot, seq })
+    }
+
+    pub fn remove(&mut self, id: LayerId) {
+        if self.slot_mut(id).is_some() {
+            self.slots[id.slot] = None;
+        }
+    }
+
+    /// Whether the layer is still there, i.e. wasn't removed and its effect
+    /// hasn't finished.
+    pub fn contains(&self, id: LayerId) -> bool {
+        matches!(&self.slots[id.slot], Some(slot) if slot.seq == id.seq)
+    }
+
+    pub fn set_opacity(&mut self, id: LayerId, opacity: u8) {
+        if let Some(slot) = self.slot_mut(id) {
+            slot.layer.opacity = opacity;
+        }
+    }
+
+    fn slot_mut(&mut self, id: LayerId) -> Option<&mut Slot<'a>> {
+        self.slots[id.slot]
+            .as_mut()
+            .filter(|slot| slot.seq == id.seq)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.iter().all(Option::is_none)
+    }
+}
+
+impl<const N: usize> Player for Compositor<'_, N> {
+    /// Renders all layers at `now` onto a black frame. Returns `false` once
+    /// no layer is left.
+    fn render(&mut self, now: Instant, frame: &mut Frame) -> bool {
+        let mut order = [0; N];
+        let mut len = 0;
+        for (i, slot) in self.slots.iter().enumerate() {
+            if slot.is_some() {
+                order[len] = i;
+                len += 1;
+            }
+        }
+        let order = &mut order[..len];
+        order.sort_unstable_by_key(|&i| {
+            let slot = self.slots[i].as_ref().unwrap();
+            (slot.layer.priority, slot.seq.wrapping_sub(self.next_seq))
+        });
+
+        *frame = [Color16::new(0, 0, 0); NUM_LEDS];
+        for &i in order.iter() {
+            let slot = self.slots[i].as_mut().unwrap();
+            let mut rendered = [Color16::new(0, 0, 0); NUM_LEDS];
+            let running = slot.effect.render(now - slot.start, &mut rendered);
+            let leds = slot.layer.leds.clone();
+            for (below, &c) in frame[leds.clone()].iter_mut().zip(&rendered[leds]) {
+                slot.layer.blend(below, c);
+            }
+            if !running {
+                self.slots[i] = None;
+            }
+        }
+        !self.is_empty()
+    }
+}
+
+impl<const N: usize> Default for Compositor<'_, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use super::*;
+
+    /// Solid color, finishes after `until`.
+    struct Solid {
+        color: Color16,
+        until: Duration,
+    }
+
+    impl Solid {
+        fn new(r: u16, g: u16, b: u16) -> Self {
+            Solid {
+                color: Color16::new(r, g, b),
+                until: Duration::MAX,
+            }
+        }
+    }
+
+    impl Effect for Solid {
+        fn render(&mut self, t: Duration, frame: &mut Frame) -> bool {
+            *frame = [self.color; NUM_LEDS];
+            t < self.until
+        }
+    }
+
+    const T0: Instant = Instant::from_micros(0);
+
+    fn render<const N: usize>(compositor: &mut Compositor<'_, N>) -> Frame {
+        let mut frame = [Color16::new(1, 2, 3); NUM_LEDS];
+        compositor.render(T0, &mut frame);
+        frame
+    }
+
+    #[test]
+    fn blend_modes() {
+        assert_eq!(BlendMode::Max.blend(100, 300), 300);
+        assert_eq!(BlendMode::Max.blend(300, 100), 300);
+        assert_eq!(BlendMode::Add.blend(100, 300), 400);
+        assert_eq!(BlendMode::Add.blend(60_000, 10_000), u16::MAX);
+        assert_eq!(BlendMode::Alpha.blend(300, 100), 100);
+        assert_eq!(BlendMode::Multiply.blend(1000, u16::MAX), 1000);
+        assert_eq!(BlendMode::Multiply.blend(1000, u16::MAX / 2), 499);
+        assert_eq!(BlendMode::Multiply.blend(1000, 0), 0);
+    }
+
+    #[test]
+    fn opacity_mixes_with_the_layer_below() {
+        let (mut below, mut above) = (Solid::new(1000, 0, 0), Solid::new(0, 2040, 0));
+        let mut compositor = Compositor::<2>::new();
+        compositor.add(&mut below, T0, Layer::new(BlendMode::Max));
+        compositor.add(&mut above, T0, Layer::new(BlendMode::Alpha).opacity(51));
+
+        // 20% of the way from the layer below to the blended one
+        assert_eq!(render(&mut compositor)[0], Color16::new(800, 408, 0));
+    }
+
+    #[test]
+    fn higher_priority_is_on_top() {
+        let (mut red, mut green) = (Solid::new(100, 0, 0), Solid::new(0, 100, 0));
+        let mut compositor = Compositor::<2>::new();
+        compositor.add(&mut red, T0, Layer::new(BlendMode::Alpha).priority(1));
+        compositor.add(&mut green, T0, Layer::new(BlendMode::Alpha));
+        assert_eq!(render(&mut compositor)[0], Color16::new(100, 0, 0));
+    }
+
+    #[test]
+    fn same_priority_stacks_in_order_added() {
+        let (mut red, mut green) = (Solid::new(100, 0, 0), Solid::new(0, 100, 0));
+        let mut compositor = Compositor::<3>::new();
+        let first = compositor
+            .add(&mut red, T0, Layer::new(BlendMode::Alpha))
+            .unwrap();
+        compositor.add(&mut green, T0, Layer::new(BlendMode::Alpha));
+        assert_eq!(render(&mut compositor)[0], Color16::new(0, 100, 0));
+
+        // a layer added into a freed slot still goes on top
+        let mut blue = Solid::new(0, 0, 100);
+        compositor.remove(first);
+        compositor.add(&mut blue, T0, Layer::new(BlendMode::Alpha));
+        assert_eq!(render(&mut compositor)[0], Color16::new(0, 0, 100));
+    }
+
+    #[test]
+    fn layer_only_covers_its_leds() {
+        let (mut base, mut side) = (Solid::new(100, 0, 0), Solid::new(0, 100, 0));
+        let mut compositor = Compositor::<2>::new();
+        compositor.add(&mut base, T0, Layer::new(BlendMode::Max));
+        compositor.add(&mut side, T0, Layer::new(BlendMode::Alpha).leds(0..10));
+
+        let frame = render(&mut compositor);
+        assert!(frame[..10].iter().all(|c| *c == Color16::new(0, 100, 0)));
+        assert!(frame[10..].iter().all(|c| *c == Color16::new(100, 0, 0)));
+    }
+
+    #[test]
+    fn reversed_leds_cover_none() {
+        let (mut base, mut side) = (Solid::new(100, 0, 0), Solid::new(0, 100, 0));
+        let mut compositor = Compositor::<2>::new();
+        compositor.add(&mut base, T0, Layer::new(BlendMode::Max));
+        let (start, end) = (20, 10);
+        compositor.add(&mut side, T0, Layer::new(BlendMode::Alpha).leds(start..end));
+        assert_eq!(render(&mut compositor), [Color16::new(100, 0, 0); NUM_LEDS]);
+    }
+
+    #[test]
+    fn ids_of_finished_layers_stay_stale() {
+        let mut flash = Solid {
+            color: Color16::new(500, 0, 0),
+            until: Duration::ZERO,
+        };
+        let mut next = Solid::new(0, 100, 0);
+        let mut compositor = Compositor::<1>::new();
+        let old = compositor
+            .add(&mut flash, T0, Layer::new(BlendMode::Max))
+            .unwrap();
+        render(&mut compositor);
+        let new = compositor
+            .add(&mut next, T0, Layer::new(BlendMode::Max))
+            .unwrap();
+
+        assert!(!compositor.contains(old));
+        compositor.set_opacity(old, 0);
+        compositor.remove(old);
+        assert!(compositor.contains(new));
+        assert_eq!(render(&mut compositor)[0], Color16::new(0, 100, 0));
+    }
+
+    #[test]
+    fn finished_layers_are_removed() {
+        let mut flash = Solid {
+            color: Color16::new(500, 0, 0),
+            until: Duration::from_millis(10),
+        };
+        let mut compositor = Compositor::<1>::new();
+        let id = compositor
+            .add(&mut flash, T0, Layer::new(BlendMode::Max))
+            .unwrap();
+
+        let mut frame = [Color16::new(0, 0, 0); NUM_LEDS];
+        assert!(compositor.render(T0 + Duration::from_millis(5), &mut frame));
+        assert!(!compositor.render(T0 + Duration::from_millis(10), &mut frame));
+        assert_eq!(frame[0].r, 500);
+        assert!(!compositor.contains(id));
+        assert!(compositor.is_empty());
+    }
+
+    #[test]
+    fn full_compositor_rejects_layers() {
+        let (mut a, mut b) = (Solid::new(1, 1, 1), Solid::new(2, 2, 2));
+        let mut compositor = Compositor::<1>::new();
+        assert!(compositor
+            .add(&mut a, T0, Layer::new(BlendMode::Max))
+            .is_some());
+        assert!(compositor
+            .add(&mut b, T0, Layer::new(BlendMode::Max))
+            .is_none());
+    }
+}
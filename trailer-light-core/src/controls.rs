@@ -0,0 +1,385 @@
+//! What the button does.
+//!
+//! [`Controls`] maps the [`Gesture`]s of the button to commands and wraps
+//! the player of the light to show their effect:
+//!
+//! - a short press switches to the next mode of [`MODES`],
+//! - a double press to the next of the [`BRIGHTNESS`] levels,
+//! - a long press turns the light off, and on again,
+//! - holding the button at boot opens the configuration menu. In it, a short
+//!   press changes the value of the setting shown, a double press goes to
+//!   the next setting and a long press leaves the menu.
+
+use crate::{
+    brake::BRAKE_RED,
+    braking::Hysteresis,
+    button::Gesture,
+    effect::{Frame, Player},
+    effects::{Scene, VAL_3},
+    layout::{Side, View, TRAILER},
+    sequencer::{boot, concat, Next, Step, Until},
+    time::Instant,
+    transition, widen, Color, Color16, NUM_LEDS,
+};
+
+/// Events of the [`MODES`] playlist.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Input {
+    NextMode,
+}
+
+/// Index of the first mode in [`MODES`], after the boot sequence.
+pub const FIRST_MODE: usize = boot::<Input>().len();
+
+/// The [`boot`] sequence, which a short press skips.
+const fn skippable_boot() -> [Step<'static, Input>; FIRST_MODE] {
+    let mut steps = boot();
+    let mut i = 0;
+    while i < steps.len() {
+        steps[i] = steps[i].branches(&[(Input::NextMode, FIRST_MODE)]);
+        i += 1;
+    }
+    steps
+}
+
+/// The [`boot`] sequence, then the modes the button cycles through: the
+/// wave, a steady dim red and blinking.
+pub static MODES: [Step<Input>; 7] = concat(
+    skippable_boot(),
+    [
+        Step::new(Scene::Wave, Until::Event(Input::NextMode)).fade_in(transition::MODE_CHANGE),
+        Step::new(
+            Scene::Solid(Color::new(VAL_3 as u8, 0, 0)),
+            Until::Event(Input::NextMode),
+        )
+        .fade_in(transition::MODE_CHANGE),
+        Step::new(Scene::Blink, Until::Event(Input::NextMode))
+            .fade_in(transition::MODE_CHANGE)
+            .then(Next::Goto(FIRST_MODE)),
+    ],
+);
+
+/// Brightness levels in percent, the light starts at the brightest.
+pub const BRIGHTNESS: [u8; 4] = [25, 50, 75, 100];
+
+/// How easily the brake light is triggered.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Sensitivity {
+    Low,
+    #[default]
+    Medium,
+    High,
+}
+
+impl Sensitivity {
+    /// Thresholds for the deceleration in milli-g.
+    pub const fn hysteresis(self) -> Hysteresis {
+        match self {
+            Sensitivity::Low => Hysteresis::new(250, 120),
+            Sensitivity::Medium => Hysteresis::new(150, 70),
+            Sensitivity::High => Hysteresis::new(100, 50),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Setting {
+    Sensitivity,
+    /// Whether the animations speed up with the riding speed.
+    FollowSpeed,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Settings {
+    pub sensitivity: Sensitivity,
+    pub follow_speed: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            sensitivity: Sensitivity::Medium,
+            follow_speed: true,
+        }
+    }
+}
+
+/// What a gesture asks the firmware to do, besides what [`Controls`] shows
+/// by itself.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// Send [`Input::NextMode`] to the [`MODES`] playlist.
+    NextMode,
+    /// The brightness changed to the given percentage.
+    Brightness(u8),
+    /// The light was turned on or off.
+    Power(bool),
+    /// The menu was opened at the setting, or closed.
+    Menu(Option<Setting>),
+    /// A setting was changed in the menu.
+    Settings(Settings),
+}
+
+/// Plays `P` as the button says: dimmed, turned off or replaced by the menu.
+pub struct Controls<P> {
+    player: P,
+    on: bool,
+    level: usize,
+    menu: Option<Setting>,
+    settings: Settings,
+}
+
+impl<P: Player> Controls<P> {
+    pub fn new(player: P) -> Self {
+        Controls {
+            player,
+            on: true,
+            level: BRIGHTNESS.len() - 1,
+            menu: None,
+            settings: Settings::default(),
+        }
+    }
+
+    /// Handles `gesture`, returns the command it results in, if any.
+    pub fn gesture(&mut self, gesture: Gesture) -> Option<Command> {
+        if !self.on {
+            if gesture != Gesture::Long {
+                return None;
+            }
+            self.on = true;
+            return Some(Command::Power(true));
+        }
+        if let Some(setting) = self.menu {
+            return match gesture {
+                Gesture::Short => {
+                    self.change(setting);
+                    Some(Command::Settings(self.settings))
+                }
+                Gesture::Double => {
+                    self.menu = Some(match setting {
+                        Setting::Sensitivity => Setting::FollowSpeed,
+                        Setting::FollowSpeed => Setting::Sensitivity,
+                    });
+                    Some(Command::Menu(self.menu))
+                }
+                Gesture::Long => {
+                    self.menu = None;
+                    Some(Command::Menu(None))
+                }
+                Gesture::HeldAtBoot => None,
+            };
+        }
+        Some(match gesture {
+            Gesture::Short => Command::NextMode,
+            Gesture::Double => {
+                self.level = (self.level + 1) % BRIGHTNESS.len();
+                Command::Brightness(self.brightness())
+            }
+            Gesture::Long => {
+                self.on = false;
+                Command::Power(false)
+            }
+            Gesture::HeldAtBoot => {
+                self.menu = Some(Setting::Sensitivity);
+                Command::Menu(self.menu)
+            }
+        })
+    }
+
+    fn change(&mut self, setting: Setting) {
+        let settings = &mut self.settings;
+        match setting {
+            Setting::Sensitivity => {
+                settings.sensitivity = match settings.sensitivity {
+                    Sensitivity::Low => Sensitivity::Medium,
+                    Sensitivity::Medium => Sensitivity::High,
+                    Sensitivity::High => Sensitivity::Low,
+                }
+            }
+            Setting::FollowSpeed => settings.follow_speed = !settings.follow_speed,
+        }
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.on
+    }
+
+    /// Brightness in percent.
+    pub fn brightness(&self) -> u8 {
+        BRIGHTNESS[self.level]
+    }
+
+    /// The setting shown in the menu, `None` if it isn't open.
+    pub fn menu(&self) -> Option<Setting> {
+        self.menu
+    }
+
+    pub fn settings(&self) -> Settings {
+        self.settings
+    }
+
+    pub fn player(&mut self) -> &mut P {
+        &mut self.player
+    }
+
+    pub fn into_player(self) -> P {
+        self.player
+    }
+
+    /// Shows the setting on the left side, one LED per setting, and its
+    /// value in the center, a pair of LEDs for the lowest value and one
+    /// more for each step up. Off is one pair, on two.
+    fn render_menu(&self, setting: Setting, frame: &mut Frame) {
+        *frame = [Color16::new(0, 0, 0); NUM_LEDS];
+        let (index, value) = match setting {
+            Setting::Sensitivity => (0, self.settings.sensitivity as usize + 1),
+            Setting::FollowSpeed => (1, self.settings.follow_speed as usize + 1),
+        };
+        let left = TRAILER.segment(Side::Left).unwrap().view();
+        left.fill(0..index + 1, widen(Color::new(255, 255, 255)), frame);
+        View::strip().mirror().fill(0..value, BRAKE_RED, frame);
+    }
+}
+
+impl<P: Player> Player for Controls<P> {
+    /// Renders the player at the brightness, the menu while it's open or
+    /// black while the light is off. The player keeps running in the
+    /// background. Returns `false` once it has finished.
+    fn render(&mut self, now: Instant, frame: &mut Frame) -> bool {
+        let running = self.player.render(now, frame);
+        if !self.on {
+            *frame = [Color16::new(0, 0, 0); NUM_LEDS];
+        } else if let Some(setting) = self.menu {
+            self.render_menu(setting, frame);
+        } else {
+            let percent = self.brightness() as u32;
+            let scale = |c: u16| (c as u32 * percent / 100) as u16;
+            for c in frame.iter_mut() {
+                *c = Color16::new(scale(c.r), scale(c.g), scale(c.b));
+            }
+        }
+        running
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sequencer::Sequencer;
+
+    /// Solid blue, never finishes.
+    struct Blue;
+
+    impl Player for Blue {
+        fn render(&mut self, _now: Instant, frame: &mut Frame) -> bool {
+            *frame = [Color16::new(0, 0, 1000); NUM_LEDS];
+            true
+        }
+    }
+
+    fn render<P: Player>(controls: &mut Controls<P>, ms: u64) -> Frame {
+        let mut frame = [Color16::default(); NUM_LEDS];
+        controls.render(Instant::from_micros(ms * 1_000), &mut frame);
+        frame
+    }
+
+    #[test]
+    fn brightness_cycles() {
+        let mut controls = Controls::new(Blue);
+        assert_eq!(render(&mut controls, 0)[0].b, 1000);
+        assert_eq!(
+            controls.gesture(Gesture::Double),
+            Some(Command::Brightness(25))
+        );
+        assert_eq!(render(&mut controls, 0)[0].b, 250);
+        for _ in 0..3 {
+            controls.gesture(Gesture::Double);
+        }
+        assert_eq!(controls.brightness(), 100);
+    }
+
+    #[test]
+    fn long_press_turns_off_and_on() {
+        let mut controls = Controls::new(Blue);
+        assert_eq!(controls.gesture(Gesture::Long), Some(Command::Power(false)));
+        assert_eq!(render(&mut controls, 0), [Color16::new(0, 0, 0); NUM_LEDS]);
+        // nothing but a long press does anything while off
+        assert_eq!(controls.gesture(Gesture::Short), None);
+        assert_eq!(controls.gesture(Gesture::Double), None);
+        assert_eq!(controls.gesture(Gesture::Long), Some(Command::Power(true)));
+        assert!(controls.is_on());
+        assert_eq!(controls.brightness(), 100);
+    }
+
+    #[test]
+    fn menu_changes_settings() {
+        let mut controls = Controls::new(Blue);
+        assert_eq!(
+            controls.gesture(Gesture::HeldAtBoot),
+            Some(Command::Menu(Some(Setting::Sensitivity)))
+        );
+        let frame = render(&mut controls, 0);
+        // first setting, value medium
+        assert_eq!(frame.iter().filter(|c| c.g > 0).count(), 1);
+        assert_eq!(frame.iter().filter(|c| **c == BRAKE_RED).count(), 4);
+
+        let Some(Command::Settings(settings)) = controls.gesture(Gesture::Short) else {
+            panic!("no settings");
+        };
+        assert_eq!(settings.sensitivity, Sensitivity::High);
+        controls.gesture(Gesture::Double);
+        let frame = render(&mut controls, 0);
+        // second setting, on
+        assert_eq!(frame.iter().filter(|c| c.g > 0).count(), 2);
+        assert_eq!(frame.iter().filter(|c| **c == BRAKE_RED).count(), 4);
+        controls.gesture(Gesture::Short);
+        assert!(!controls.settings().follow_speed);
+        // off is still shown
+        let frame = render(&mut controls, 0);
+        assert_eq!(frame.iter().filter(|c| **c == BRAKE_RED).count(), 2);
+        assert_eq!(controls.gesture(Gesture::Long), Some(Command::Menu(None)));
+        assert_eq!(render(&mut controls, 0)[0].b, 1000);
+    }
+
+    #[test]
+    fn boots_into_the_first_mode() {
+        let mut controls = Controls::new(Sequencer::new(&MODES));
+        controls.player().start(Instant::default());
+        let mut ms = 0;
+        while controls.player().current() != Some(FIRST_MODE) {
+            assert!(ms < 10_000, "still booting");
+            render(&mut controls, ms);
+            ms += 10;
+        }
+        // the turn-on animation is held for five seconds
+        assert!(ms > 5_500, "{}", ms);
+        render(&mut controls, 20_000);
+        assert_eq!(controls.player().current(), Some(FIRST_MODE));
+    }
+
+    #[test]
+    fn short_press_skips_the_boot_sequence() {
+        let mut controls = Controls::new(Sequencer::new(&MODES));
+        controls.player().start(Instant::default());
+        render(&mut controls, 600);
+        assert_eq!(controls.player().current(), Some(1));
+        controls
+            .player()
+            .event(Input::NextMode, Instant::from_micros(600_000));
+        assert_eq!(controls.player().current(), Some(FIRST_MODE));
+    }
+
+    #[test]
+    fn short_press_cycles_the_modes() {
+        let mut controls = Controls::new(Sequencer::new(&MODES));
+        controls.player().start(Instant::default());
+        render(&mut controls, 0);
+        for expected in [4, 5, 6, 4] {
+            assert_eq!(controls.gesture(Gesture::Short), Some(Command::NextMode));
+            controls
+                .player()
+                .event(Input::NextMode, Instant::from_micros(10_000));
+            assert_eq!(controls.player().current(), Some(expected));
+        }
+    }
+}
@@ -0,0 +1,102 @@
+//! Temporal dithering from the 16 bit frame buffer down to the 8 bits the
+//! LEDs take.
+//!
+//! Each channel keeps the rounding error of the previous frames and adds it
+//! to the next one, so a value between two 8 bit steps alternates between
+//! them and averages out to the right brightness. This is what makes slow
+//! fades at low brightness smooth instead of visibly stepping.
+//!
+//! The dithering only depends on the frames passed in, so the output is
+//! fully deterministic.
+
+use crate::{Color, Color16};
+
+/// One 8 bit step in units of the 16 bit frame buffer is `65535 / 255`,
+/// computing in units of `1 / 65535` of an 8 bit step keeps everything exact.
+const STEP: u32 = u16::MAX as u32;
+
+pub struct TemporalDither<const N: usize> {
+    residual: [[u32; 3]; N],
+}
+
+impl<const N: usize> TemporalDither<N> {
+    pub fn new() -> Self {
+        TemporalDither {
+            residual: [[0; 3]; N],
+        }
+    }
+
+    /// Forgets the accumulated rounding errors.
+    pub fn reset(&mut self) {
+        self.residual = [[0; 3]; N];
+    }
+
+    pub fn dither(&mut self, frame: &[Color16; N], out: &mut [Color; N]) {
+        for ((c, o), residual) in frame.iter().zip(out.iter_mut()).zip(&mut self.residual) {
+            let channel = |v: u16, residual: &mut u32| {
+                let acc = v as u32 * 255 + *residual;
+                *residual = acc % STEP;
+                (acc / STEP) as u8
+            };
+            let [r, g, b] = residual;
+            *o = Color::new(channel(c.r, r), channel(c.g, g), channel(c.b, b));
+        }
+    }
+}
+
+impl<const N: usize> Default for TemporalDither<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::widen;
+
+    #[test]
+    fn exact_values_are_kept() {
+        let mut dither = TemporalDither::<3>::new();
+        let frame = [
+            widen(Color::new(0, 1, 2)),
+            widen(Color::new(10, 60, 255)),
+            widen(Color::new(128, 0, 0)),
+        ];
+        let mut out = [Color::default(); 3];
+        for _ in 0..10 {
+            dither.dither(&frame, &mut out);
+            assert_eq!(out, [Color::new(0, 1, 2), Color::new(10, 60, 255), Color::new(128, 0, 0)]);
+        }
+    }
+
+    #[test]
+    fn fractions_average_out() {
+        let mut dither = TemporalDither::<1>::new();
+        // about 10.25 in 8 bit units
+        let frame = [Color16::new(10 * 257 + 64, 0, 0)];
+        let mut out = [Color::default()];
+        let mut sum = 0;
+        for _ in 0..1000 {
+            dither.dither(&frame, &mut out);
+            assert!(out[0].r == 10 || out[0].r == 11);
+            sum += out[0].r as u32;
+        }
+        assert_eq!(sum, (10 * 257 + 64) * 255 * 1000 / 65535);
+    }
+
+    #[test]
+    fn is_deterministic() {
+        let run = || {
+            let mut dither = TemporalDither::<1>::new();
+            let mut out = [Color::default()];
+            (0..100u16)
+                .map(|i| {
+                    dither.dither(&[Color16::new(i * 37, i * 11, 300)], &mut out);
+                    out[0]
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(run(), run());
+    }
+}
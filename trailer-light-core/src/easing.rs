@@ -0,0 +1,128 @@
+//! Easing curves for transitions and the motion of sweeps.
+
+/// Maps the linear progress of a transition to the progress shown.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Easing {
+    #[default]
+    Linear,
+    /// Starts slow, ends fast.
+    EaseIn,
+    /// Starts fast, ends slow.
+    EaseOut,
+    /// Slow at both ends.
+    EaseInOut,
+    /// Like [`EaseIn`](Easing::EaseIn), more pronounced.
+    EaseInCubic,
+    /// Like [`EaseOut`](Easing::EaseOut), more pronounced.
+    EaseOutCubic,
+    /// Like [`EaseInOut`](Easing::EaseInOut), more pronounced.
+    EaseInOutCubic,
+    /// Falls onto the end and bounces off it a few times, each time less.
+    Bounce,
+}
+
+impl Easing {
+    /// Eased progress for the linear progress `x`. Both are in `0.0..=1.0`,
+    /// `x` outside of it is clamped.
+    pub fn apply(self, x: f32) -> f32 {
+        let x = x.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => x,
+            Easing::EaseIn => x * x,
+            Easing::EaseOut => 1.0 - (1.0 - x) * (1.0 - x),
+            Easing::EaseInOut => {
+                if x < 0.5 {
+                    2.0 * x * x
+                } else {
+                    1.0 - 2.0 * (1.0 - x) * (1.0 - x)
+                }
+            }
+            Easing::EaseInCubic => x * x * x,
+            Easing::EaseOutCubic => 1.0 - (1.0 - x) * (1.0 - x) * (1.0 - x),
+            Easing::EaseInOutCubic => {
+                if x < 0.5 {
+                    4.0 * x * x * x
+                } else {
+                    1.0 - 4.0 * (1.0 - x) * (1.0 - x) * (1.0 - x)
+                }
+            }
+            Easing::Bounce => {
+                // parabolas of decreasing height, touching 1 at their ends
+                const N: f32 = 7.5625;
+                const D: f32 = 2.75;
+                if x < 1.0 / D {
+                    N * x * x
+                } else if x < 2.0 / D {
+                    let x = x - 1.5 / D;
+                    N * x * x + 0.75
+                } else if x < 2.5 / D {
+                    let x = x - 2.25 / D;
+                    N * x * x + 0.9375
+                } else {
+                    let x = x - 2.625 / D;
+                    N * x * x + 0.984375
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [Easing; 8] = [
+        Easing::Linear,
+        Easing::EaseIn,
+        Easing::EaseOut,
+        Easing::EaseInOut,
+        Easing::EaseInCubic,
+        Easing::EaseOutCubic,
+        Easing::EaseInOutCubic,
+        Easing::Bounce,
+    ];
+
+    #[test]
+    fn starts_at_zero_and_ends_at_one() {
+        for easing in ALL {
+            assert_eq!(easing.apply(0.0), 0.0, "{:?}", easing);
+            assert_eq!(easing.apply(1.0), 1.0, "{:?}", easing);
+            assert_eq!(easing.apply(-1.0), 0.0, "{:?}", easing);
+            assert_eq!(easing.apply(2.0), 1.0, "{:?}", easing);
+        }
+    }
+
+    #[test]
+    fn is_monotonic() {
+        for easing in ALL.into_iter().filter(|e| *e != Easing::Bounce) {
+            let mut last = 0.0;
+            for i in 0..=100 {
+                let y = easing.apply(i as f32 / 100.0);
+                assert!(y >= last, "{:?} at {}", easing, i);
+                last = y;
+            }
+        }
+    }
+
+    #[test]
+    fn shapes() {
+        assert!(Easing::EaseIn.apply(0.5) < 0.5);
+        assert!(Easing::EaseOut.apply(0.5) > 0.5);
+        assert_eq!(Easing::EaseInOut.apply(0.5), 0.5);
+        assert!(Easing::EaseInOut.apply(0.25) < 0.25);
+        assert!(Easing::EaseInCubic.apply(0.5) < Easing::EaseIn.apply(0.5));
+        assert!(Easing::EaseOutCubic.apply(0.5) > Easing::EaseOut.apply(0.5));
+        assert_eq!(Easing::EaseInOutCubic.apply(0.5), 0.5);
+    }
+
+    #[test]
+    fn bounce_touches_the_end_and_stays_below() {
+        for x in [1.0 / 2.75, 2.0 / 2.75, 2.5 / 2.75] {
+            assert!((Easing::Bounce.apply(x) - 1.0).abs() < 1e-5, "at {}", x);
+        }
+        for i in 0..=100 {
+            assert!(Easing::Bounce.apply(i as f32 / 100.0) <= 1.0 + 1e-5);
+        }
+        assert!(Easing::Bounce.apply(0.55) < 0.8);
+    }
+}
@@ -0,0 +1,381 @@
+//! Effects and the runner playing them.
+//!
+//! An [`Effect`] is a function of the time since it started: it renders one
+//! frame for a given time and tells whether it's still running. The
+//! [`Runner`] keeps track of the active effect and its start time, so the
+//! effects themselves don't need to know about the clock or the LEDs.
+
+use core::time::Duration;
+
+use crate::{
+    time::Instant,
+    transition::{crossfade, Transition},
+    Color16, NUM_LEDS,
+};
+
+/// Frame buffer effects render into.
+pub type Frame = [Color16; NUM_LEDS];
+
+pub trait Effect {
+    /// Renders the effect `t` after its start into `frame`. Returns `false`
+    /// once the effect has finished, the frame is then its last one.
+    fn render(&mut self, t: Duration, frame: &mut Frame) -> bool;
+
+    /// How long the effect runs, `None` if it runs forever or can't tell in
+    /// advance.
+    fn duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+impl<E: Effect + ?Sized> Effect for &mut E {
+    fn render(&mut self, t: Duration, frame: &mut Frame) -> bool {
+        (**self).render(t, frame)
+    }
+
+    fn duration(&self) -> Option<Duration> {
+        (**self).duration()
+    }
+}
+
+/// Renders frames at absolute points in time, keeping track of when its
+/// effects started. Implemented by the [`Runner`] and the things built on
+/// top of it, see [`TrailerLight::play`](crate::TrailerLight::play).
+pub trait Player {
+    /// Renders the frame at `now`. Returns `false` once there is nothing left
+    /// to play.
+    fn render(&mut self, now: Instant, frame: &mut Frame) -> bool;
+}
+
+/// Drives whichever effect is active.
+///
+/// Switching effects with [`start`](Runner::start) is a hard cut,
+/// [`crossfade`](Runner::crossfade) blends over from what was shown before.
+///
+/// `E` is usually `&mut dyn Effect`, so effects of different types can follow
+/// each other.
+pub struct Runner<E> {
+    active: Option<(E, Instant)>,
+    fade: Option<Fade<E>>,
+    // last frame of the active effect, kept once it has finished
+    last: Frame,
+    // last frame returned by `render`, where a crossfade starts
+    shown: Frame,
+}
+
+struct Fade<E> {
+    from: Option<(E, Instant)>,
+    from_frame: Frame,
+    start: Instant,
+    transition: Transition,
+}
+
+impl<E: Effect> Runner<E> {
+    pub fn new() -> Self {
+        Runner {
+            active: None,
+            fade: None,
+            last: [Color16::new(0, 0, 0); NUM_LEDS],
+            shown: [Color16::new(0, 0, 0); NUM_LEDS],
+        }
+    }
+
+    /// Starts `effect` at `now`, replacing the active one.
+    pub fn start(&mut self, effect: E, now: Instant) {
+        self.active = Some((effect, now));
+        self.fade = None;
+    }
+
+    /// Starts `effect` at `now` and fades over to it from the active effect,
+    /// which keeps running until the transition is complete.
+    ///
+    /// If no effect is active, or a transition is already running, it fades
+    /// from the last frame shown instead.
+    pub fn crossfade(&mut self, effect: E, now: Instant, transition: Transition) {
+        let from = match self.fade {
+            Some(_) => None,
+            None => self.active.take(),
+        };
+        self.active = Some((effect, now));
+        self.fade = Some(Fade {
+            from,
+            from_frame: self.shown,
+            start: now,
+            transition,
+        });
+    }
+
+    /// Starts `effect` at `now` like [`start`](Runner::start), but lets a
+    /// running transition continue. Used to play an effect again.
+    pub fn restart(&mut self, effect: E, now: Instant) {
+        self.active = Some((effect, now));
+    }
+
+    pub fn stop(&mut self) {
+        self.active = None;
+        self.fade = None;
+    }
+
+    /// Whether the active effect hasn't finished yet. A transition might
+    /// still be running after it has.
+    pub fn has_effect(&self) -> bool {
+        self.active.is_some()
+    }
+
+    /// Whether an effect or a transition is still running.
+    pub fn is_running(&self) -> bool {
+        self.active.is_some() || self.fade.is_some()
+    }
+}
+
+impl<E: Effect> Player for Runner<E> {
+    /// Renders the active effect at `now`. Returns `false` if nothing is
+    /// running anymore, in which case `frame` is left as it is unless the
+    /// effect just rendered its last frame.
+    fn render(&mut self, now: Instant, frame: &mut Frame) -> bool {
+        if let Some((effect, start)) = &mut self.active {
+            if !effect.render(now - *start, &mut self.last) {
+                self.active = None;
+            }
+        } else if self.fade.is_none() {
+            return false;
+        }
+        *frame = self.last;
+
+        if let Some(fade) = &mut self.fade {
+            if let Some((effect, start)) = &mut fade.from {
+                if !effect.render(now - *start, &mut fade.from_frame) {
+                    fade.from = None;
+                }
+            }
+            let t = now - fade.start;
+            crossfade(&fade.from_frame, frame, fade.transition.weight(t));
+            // easings may reach full weight before the end, e.g. a bounce
+            if t >= fade.transition.duration {
+                self.fade = None;
+            }
+        }
+        self.shown = *frame;
+        self.is_running()
+    }
+}
+
+impl<E: Effect> Default for Runner<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Tempo at which [`Tempo`] plays normally.
+pub const NORMAL_TEMPO: u32 = 100;
+
+/// Plays `P` faster or slower, e.g. with the riding speed.
+///
+/// The player sees a time that passes at the tempo, starting at the real
+/// time of the first frame. Effects started on it later have to be started
+/// at [`now`](Tempo::now) rather than the real time.
+pub struct Tempo<P> {
+    player: P,
+    percent: u32,
+    last: Option<Instant>,
+    now: Instant,
+}
+
+impl<P: Player> Tempo<P> {
+    pub fn new(player: P) -> Self {
+        Tempo {
+            player,
+            percent: NORMAL_TEMPO,
+            last: None,
+            now: Instant::default(),
+        }
+    }
+
+    /// Sets the tempo in percent, [`NORMAL_TEMPO`] being the normal speed.
+    /// Changes apply from the last frame on, so the effects don't jump.
+    pub fn set_tempo(&mut self, percent: u32) {
+        self.percent = percent;
+    }
+
+    pub fn tempo(&self) -> u32 {
+        self.percent
+    }
+
+    /// Time the player saw at the last frame.
+    pub fn now(&self) -> Instant {
+        self.now
+    }
+
+    pub fn player(&mut self) -> &mut P {
+        &mut self.player
+    }
+
+    pub fn into_player(self) -> P {
+        self.player
+    }
+}
+
+impl<P: Player> Player for Tempo<P> {
+    fn render(&mut self, now: Instant, frame: &mut Frame) -> bool {
+        self.now = match self.last {
+            None => now,
+            Some(last) => self.now + (now - last) * self.percent / NORMAL_TEMPO,
+        };
+        self.last = Some(now);
+        self.player.render(self.now, frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::easing::Easing;
+
+    /// Lights one more LED every millisecond, finishes at the end of the strip.
+    struct Fill;
+
+    impl Effect for Fill {
+        fn render(&mut self, t: Duration, frame: &mut Frame) -> bool {
+            let n = (t.as_millis() as usize).min(NUM_LEDS);
+            for (i, c) in frame.iter_mut().enumerate() {
+                *c = Color16::new(if i < n { 1 } else { 0 }, 0, 0);
+            }
+            n < NUM_LEDS
+        }
+    }
+
+    fn lit(frame: &Frame) -> usize {
+        frame.iter().filter(|c| c.r > 0).count()
+    }
+
+    #[test]
+    fn renders_relative_to_start() {
+        let mut fill = Fill;
+        let mut runner = Runner::new();
+        let mut frame = [Color16::default(); NUM_LEDS];
+        runner.start(&mut fill, Instant::from_micros(5_000));
+
+        assert!(runner.render(Instant::from_micros(8_000), &mut frame));
+        assert_eq!(lit(&frame), 3);
+    }
+
+    #[test]
+    fn finished_effect_is_dropped() {
+        let mut fill = Fill;
+        let mut runner = Runner::new();
+        let mut frame = [Color16::default(); NUM_LEDS];
+        runner.start(&mut fill, Instant::from_micros(0));
+
+        let end = Instant::from_micros(NUM_LEDS as u64 * 1_000);
+        assert!(!runner.render(end, &mut frame));
+        assert_eq!(lit(&frame), NUM_LEDS);
+        assert!(!runner.is_running());
+
+        // nothing is rendered anymore
+        frame = [Color16::default(); NUM_LEDS];
+        assert!(!runner.render(end, &mut frame));
+        assert_eq!(lit(&frame), 0);
+    }
+
+    /// Solid red of the given brightness.
+    struct Solid(u16);
+
+    impl Effect for Solid {
+        fn render(&mut self, _t: Duration, frame: &mut Frame) -> bool {
+            *frame = [Color16::new(self.0, 0, 0); NUM_LEDS];
+            true
+        }
+    }
+
+    fn at_ms(ms: u64) -> Instant {
+        Instant::from_micros(ms * 1_000)
+    }
+
+    #[test]
+    fn crossfade_blends_from_the_active_effect() {
+        let (mut from, mut to) = (Solid(1000), Solid(3000));
+        let mut runner = Runner::new();
+        let mut frame = [Color16::default(); NUM_LEDS];
+        runner.start(&mut from, at_ms(0));
+        runner.render(at_ms(5), &mut frame);
+
+        let transition = Transition::new(Duration::from_millis(100), Easing::Linear);
+        runner.crossfade(&mut to, at_ms(10), transition);
+        runner.render(at_ms(10), &mut frame);
+        assert_eq!(frame[0].r, 1000);
+        runner.render(at_ms(60), &mut frame);
+        assert_eq!(frame[0].r, 1999);
+        assert!(runner.render(at_ms(110), &mut frame));
+        assert_eq!(frame[0].r, 3000);
+        assert!(runner.fade.is_none());
+    }
+
+    #[test]
+    fn crossfade_from_a_finished_effect_uses_its_last_frame() {
+        let (mut fill, mut to) = (Fill, Solid(0));
+        let mut runner = Runner::<&mut dyn Effect>::new();
+        let mut frame = [Color16::default(); NUM_LEDS];
+        runner.start(&mut fill, at_ms(0));
+        assert!(!runner.render(at_ms(1_000), &mut frame));
+
+        let transition = Transition::new(Duration::from_millis(100), Easing::Linear);
+        runner.crossfade(&mut to, at_ms(2_000), transition);
+        assert!(runner.render(at_ms(2_000), &mut frame));
+        assert_eq!(lit(&frame), NUM_LEDS);
+        assert!(runner.render(at_ms(2_050), &mut frame));
+        assert!(runner.render(at_ms(2_100), &mut frame));
+        assert_eq!(lit(&frame), 0);
+    }
+
+    #[test]
+    fn crossfade_during_a_crossfade_starts_from_what_is_shown() {
+        let (mut a, mut b, mut c) = (Solid(0), Solid(1000), Solid(1000));
+        let mut runner = Runner::new();
+        let mut frame = [Color16::default(); NUM_LEDS];
+        let transition = Transition::new(Duration::from_millis(100), Easing::Linear);
+        runner.start(&mut a, at_ms(0));
+        runner.crossfade(&mut b, at_ms(0), transition);
+        runner.render(at_ms(50), &mut frame);
+        assert_eq!(frame[0].r, 499);
+
+        runner.crossfade(&mut c, at_ms(50), transition);
+        runner.render(at_ms(50), &mut frame);
+        assert_eq!(frame[0].r, 499);
+    }
+
+    #[test]
+    fn crossfade_runs_for_the_whole_transition() {
+        let (mut from, mut to) = (Solid(0), Solid(1000));
+        let mut runner = Runner::new();
+        let mut frame = [Color16::default(); NUM_LEDS];
+        runner.start(&mut from, at_ms(0));
+        // the bounce touches full weight after 400 ms, then falls back
+        let transition = Transition::new(Duration::from_millis(1_100), Easing::Bounce);
+        runner.crossfade(&mut to, at_ms(0), transition);
+        for ms in 0..=600 {
+            runner.render(at_ms(ms), &mut frame);
+        }
+        assert_eq!(frame[0].r, 749);
+        runner.render(at_ms(1_100), &mut frame);
+        assert_eq!(frame[0].r, 1000);
+        assert!(runner.fade.is_none());
+    }
+
+    #[test]
+    fn tempo_changes_apply_from_the_last_frame() {
+        let mut fill = Fill;
+        let mut runner = Runner::new();
+        runner.start(&mut fill, at_ms(100));
+        let mut tempo = Tempo::new(runner);
+        let mut frame = [Color16::default(); NUM_LEDS];
+
+        tempo.render(at_ms(100), &mut frame);
+        tempo.set_tempo(200);
+        tempo.render(at_ms(110), &mut frame);
+        assert_eq!(lit(&frame), 20);
+        tempo.set_tempo(50);
+        tempo.render(at_ms(120), &mut frame);
+        assert_eq!(lit(&frame), 25);
+        assert_eq!(tempo.now(), at_ms(125));
+    }
+}
@@ -0,0 +1,432 @@
+//! The built-in effects of the trailer light.
+
+use core::time::Duration;
+
+use crate::{
+    color::{scale, SweepColors},
+    combinator::{Chain, EffectExt},
+    easing::Easing,
+    effect::{Effect, Frame},
+    kernel::Kernel,
+    layout::View,
+    particles::{Edge, Particle, ParticleSystem},
+    vm::Vm,
+    widen, Color, Color16, NUM_LEDS,
+};
+
+#[cfg(feature = "fixed-point")]
+use crate::fixed::FixedAnimationContext as AnimationContext;
+#[cfg(not(feature = "fixed-point"))]
+use crate::AnimationContext;
+
+const X_START: f32 = -3.0;
+const X_END: f32 = (NUM_LEDS / 2 + 3) as f32;
+// About 0.13 LEDs per frame at the roughly 3 ms a frame takes on the
+// ESP32-C3.
+const SPEED: f32 = 43.0; // LEDs per second
+const HB: f32 = 3.0;
+const VAL_0: f32 = 0.0;
+const VAL_1: f32 = 10.0;
+const VAL_2: f32 = 30.0;
+pub(crate) const VAL_3: f32 = 60.0;
+const HIGHLIGHT_1: f32 = 30.0;
+const HIGHLIGHT_2: f32 = 60.0;
+const HIGHLIGHT_3: f32 = 150.0;
+const HIGHLIGHT_4: f32 = 255.0;
+
+/// A sweep on a [`View`], by default on both halves of the strip, mirrored
+/// at the center. LEDs outside of the view are left as they are.
+pub struct Sweep {
+    ctx: AnimationContext,
+    paint: Paint,
+    view: View,
+}
+
+enum Paint {
+    /// The brightness values of the context in one color.
+    Tint(Color),
+    Colors(SweepColors),
+}
+
+impl Sweep {
+    /// Red sweep.
+    pub fn new(ctx: AnimationContext) -> Self {
+        Sweep {
+            ctx,
+            paint: Paint::Tint(Color::new(255, 0, 0)),
+            view: View::strip().mirror(),
+        }
+    }
+
+    /// Plays the sweep on `view`, its virtual LED 0 is position 0 of the
+    /// context.
+    pub fn view(mut self, view: View) -> Self {
+        self.view = view;
+        self
+    }
+
+    /// Shows the brightness values of the context in `color`.
+    pub fn tint(mut self, color: Color) -> Self {
+        self.paint = Paint::Tint(color);
+        self
+    }
+
+    /// Uses `colors` instead of the brightness values of the context.
+    pub fn colors(mut self, colors: SweepColors) -> Self {
+        self.paint = Paint::Colors(colors);
+        self
+    }
+}
+
+impl Effect for Sweep {
+    fn render(&mut self, t: Duration, frame: &mut Frame) -> bool {
+        let len = self.view.len();
+        let mut c = [Color16::new(0, 0, 0); NUM_LEDS];
+        let running = match &self.paint {
+            Paint::Tint(color) => {
+                let mut v = [0; NUM_LEDS];
+                let running = self.ctx.render(t, &mut v[..len]);
+                for (c, &v) in c.iter_mut().zip(&v[..len]) {
+                    *c = scale(*color, v);
+                }
+                running
+            }
+            Paint::Colors(colors) => self.ctx.render_colors(t, colors, &mut c[..len]),
+        };
+        self.view.write(&c[..len], frame);
+        running
+    }
+
+    fn duration(&self) -> Option<Duration> {
+        Some(self.ctx.duration())
+    }
+}
+
+/// Whether a light switching every `period`, starting on, is on at `t`.
+fn flash_on(t: Duration, period: Duration) -> bool {
+    (t.as_micros() / period.as_micros()).is_multiple_of(2)
+}
+
+/// Blinks the four center LEDs twice.
+pub struct Blink;
+
+impl Blink {
+    // on each half
+    const NUM_BLINKING: usize = 2;
+    const PERIOD: Duration = Duration::from_millis(500);
+    const DURATION: Duration = Duration::from_millis(2000);
+}
+
+impl Effect for Blink {
+    fn render(&mut self, t: Duration, frame: &mut Frame) -> bool {
+        let running = t < Self::DURATION;
+        let on = running && flash_on(t, Self::PERIOD);
+        let color = widen(Color::new(if on { VAL_1 as u8 } else { 0 }, 0, 0));
+        *frame = [Color16::new(0, 0, 0); NUM_LEDS];
+        View::strip()
+            .mirror()
+            .fill(0..Self::NUM_BLINKING, color, frame);
+        running
+    }
+
+    fn duration(&self) -> Option<Duration> {
+        Some(Self::DURATION)
+    }
+}
+
+/// Three sweeps, each leaving the strip a bit brighter, ending at the
+/// brightness of the [`Wave`]. Each sweep speeds up and settles at its end.
+///
+/// The sweeps are set up again for every frame instead of being kept, they
+/// would take three times the memory of any other built-in effect.
+#[derive(Default)]
+pub struct TurnOn;
+
+impl TurnOn {
+    pub const fn new() -> Self {
+        TurnOn
+    }
+
+    fn sweeps() -> Chain<Chain<Sweep, Sweep>, Sweep> {
+        let sweep = |from, to, bb, tb, hb| {
+            let ctx = AnimationContext::new(from, to, SPEED, bb, tb, hb, HB);
+            Sweep::new(ctx.easing(Easing::EaseInOut))
+        };
+        sweep(X_START, X_END, VAL_0, VAL_1, HIGHLIGHT_1)
+            .chain(sweep(X_END, X_START, VAL_1, VAL_2, HIGHLIGHT_2))
+            .chain(sweep(X_START, X_END, VAL_2, VAL_3, HIGHLIGHT_3))
+    }
+}
+
+impl Effect for TurnOn {
+    fn render(&mut self, t: Duration, frame: &mut Frame) -> bool {
+        Self::sweeps().render(t, frame)
+    }
+
+    fn duration(&self) -> Option<Duration> {
+        Self::sweeps().duration()
+    }
+}
+
+/// A bright highlight slowly moving over the strip, meant to be repeated.
+pub struct Wave(Sweep);
+
+impl Wave {
+    pub fn new() -> Self {
+        Wave(Sweep::new(AnimationContext::new(
+            X_START,
+            X_END,
+            SPEED / 3.0,
+            VAL_3,
+            VAL_3,
+            HIGHLIGHT_4,
+            HB * 2.0,
+        )))
+    }
+}
+
+impl Default for Wave {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Effect for Wave {
+    fn render(&mut self, t: Duration, frame: &mut Frame) -> bool {
+        self.0.render(t, frame)
+    }
+
+    fn duration(&self) -> Option<Duration> {
+        self.0.duration()
+    }
+}
+
+/// Flashes the whole strip at full red five times.
+pub struct EmergencyBrake;
+
+impl EmergencyBrake {
+    const PERIOD: Duration = Duration::from_millis(100);
+    const DURATION: Duration = Duration::from_millis(1000);
+}
+
+impl Effect for EmergencyBrake {
+    fn render(&mut self, t: Duration, frame: &mut Frame) -> bool {
+        let running = t < Self::DURATION;
+        let on = running && flash_on(t, Self::PERIOD);
+        *frame = [widen(Color::new(if on { 255 } else { 0 }, 0, 0)); NUM_LEDS];
+        running
+    }
+
+    fn duration(&self) -> Option<Duration> {
+        Some(Self::DURATION)
+    }
+}
+
+/// The whole strip in one color, never finishes.
+pub struct Solid(pub Color);
+
+impl Effect for Solid {
+    fn render(&mut self, _t: Duration, frame: &mut Frame) -> bool {
+        *frame = [widen(self.0); NUM_LEDS];
+        true
+    }
+}
+
+/// Leaves the frame as it is, never finishes. Played by a
+/// [`Runner`](crate::effect::Runner) it keeps showing the last frame of the
+/// effect before.
+pub struct Hold;
+
+impl Effect for Hold {
+    fn render(&mut self, _t: Duration, _frame: &mut Frame) -> bool {
+        true
+    }
+}
+
+/// A highlight bouncing between the ends of the strip, leaving a short
+/// trail.
+pub fn scanner() -> ParticleSystem<1> {
+    let mut scanner = ParticleSystem::new(Edge::Bounce)
+        .ambient(Color::new(VAL_1 as u8, 0, 0))
+        .trail(Duration::from_millis(300));
+    scanner.spawn(Particle::new(0.0, SPEED));
+    scanner
+}
+
+/// Two highlights chasing each other around the strip with long trails.
+pub fn comets() -> ParticleSystem<2> {
+    let mut comets = ParticleSystem::new(Edge::Wrap)
+        .ambient(Color::new(VAL_1 as u8, 0, 0))
+        .trail(Duration::from_millis(800))
+        .kernel(Kernel::Cosine);
+    comets.spawn(Particle::new(0.0, SPEED / 2.0).width(1.5));
+    comets.spawn(Particle::new((NUM_LEDS / 2) as f32, SPEED / 2.0).width(1.5));
+    comets
+}
+
+/// Drops lighting up at random and slowly fading away.
+pub fn rain() -> ParticleSystem<12> {
+    ParticleSystem::new(Edge::Vanish)
+        .ambient(Color::new(VAL_1 as u8, 0, 0))
+        .kernel(Kernel::Gaussian)
+        .spawn_every(Duration::from_millis(150), |rng| {
+            let position = rng.range(0.0, NUM_LEDS as f32);
+            Particle::new(position, rng.range(-2.0, 2.0))
+                .width(rng.range(1.0, 3.0))
+                .color(Color::new(HIGHLIGHT_3 as u8, 0, 0))
+                .decay(rng.range(0.5, 1.5))
+        })
+}
+
+/// One of the effects above with its parameters, as plain data for the
+/// [`Sequencer`](crate::sequencer::Sequencer).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scene {
+    Hold,
+    Solid(Color),
+    Blink,
+    TurnOn,
+    Wave,
+    EmergencyBrake,
+    /// An effect program, see [`vm`](crate::vm).
+    Program(&'static [u8]),
+}
+
+impl Scene {
+    pub fn effect(self) -> SceneEffect {
+        match self {
+            Scene::Hold => SceneEffect::Hold(Hold),
+            Scene::Solid(color) => SceneEffect::Solid(Solid(color)),
+            Scene::Blink => SceneEffect::Blink(Blink),
+            Scene::TurnOn => SceneEffect::TurnOn(TurnOn::new()),
+            Scene::Wave => SceneEffect::Wave(Wave::new()),
+            Scene::EmergencyBrake => SceneEffect::EmergencyBrake(EmergencyBrake),
+            Scene::Program(code) => SceneEffect::Program(Vm::new(code)),
+        }
+    }
+}
+
+/// The effect of a [`Scene`].
+// A program keeps its own frame, which makes it by far the largest. There's
+// no allocator to box it, and only the effects being played are held.
+#[allow(clippy::large_enum_variant)]
+pub enum SceneEffect {
+    Hold(Hold),
+    Solid(Solid),
+    Blink(Blink),
+    TurnOn(TurnOn),
+    Wave(Wave),
+    EmergencyBrake(EmergencyBrake),
+    Program(Vm<'static>),
+}
+
+impl Effect for SceneEffect {
+    fn render(&mut self, t: Duration, frame: &mut Frame) -> bool {
+        match self {
+            SceneEffect::Hold(e) => e.render(t, frame),
+            SceneEffect::Solid(e) => e.render(t, frame),
+            SceneEffect::Blink(e) => e.render(t, frame),
+            SceneEffect::TurnOn(e) => e.render(t, frame),
+            SceneEffect::Wave(e) => e.render(t, frame),
+            SceneEffect::EmergencyBrake(e) => e.render(t, frame),
+            SceneEffect::Program(e) => e.render(t, frame),
+        }
+    }
+
+    fn duration(&self) -> Option<Duration> {
+        match self {
+            SceneEffect::Hold(e) => e.duration(),
+            SceneEffect::Solid(e) => e.duration(),
+            SceneEffect::Blink(e) => e.duration(),
+            SceneEffect::TurnOn(e) => e.duration(),
+            SceneEffect::Wave(e) => e.duration(),
+            SceneEffect::EmergencyBrake(e) => e.duration(),
+            SceneEffect::Program(e) => e.duration(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(effect: &mut impl Effect, ms: u64) -> (bool, Frame) {
+        let mut frame = [Color16::new(1, 1, 1); NUM_LEDS];
+        let running = effect.render(Duration::from_millis(ms), &mut frame);
+        (running, frame)
+    }
+
+    #[test]
+    fn blink_lights_the_center_twice() {
+        let lit = |frame: &Frame| {
+            (0..NUM_LEDS)
+                .filter(|&i| frame[i].r > 0)
+                .collect::<Vec<_>>()
+        };
+        for (ms, on) in [
+            (0, true),
+            (499, true),
+            (500, false),
+            (1000, true),
+            (1999, false),
+        ] {
+            let (running, frame) = render(&mut Blink, ms);
+            assert!(running);
+            assert_eq!(lit(&frame).len(), if on { 4 } else { 0 }, "at {} ms", ms);
+        }
+        assert_eq!(lit(&render(&mut Blink, 0).1), [27, 28, 29, 30]);
+
+        let (running, frame) = render(&mut Blink, 2000);
+        assert!(!running);
+        assert!(frame.iter().all(|c| *c == Color16::new(0, 0, 0)));
+    }
+
+    #[test]
+    fn turn_on_plays_the_sweeps_in_order() {
+        let mut turn_on = TurnOn::new();
+        let sweep = turn_on.duration().unwrap() / 3;
+
+        // the second sweep runs from the ends towards the center
+        let (_, frame) = render(&mut turn_on, sweep.as_millis() as u64 * 3 / 2);
+        assert!(frame[0].r > frame[NUM_LEDS / 2].r);
+
+        let (running, frame) = render(&mut turn_on, sweep.as_millis() as u64 * 5 / 2);
+        assert!(running);
+        assert!(frame.iter().any(|c| c.r > (VAL_3 * 257.0) as u16));
+
+        let (running, frame) = render(&mut turn_on, 3 * sweep.as_millis() as u64 + 10);
+        assert!(!running);
+        assert!(frame.iter().all(|c| c.r == (VAL_3 * 257.0) as u16));
+    }
+
+    #[test]
+    fn emergency_brake_flashes_five_times() {
+        let mut flashes = 0;
+        let mut was_on = false;
+        for ms in 0..1000 {
+            let (running, frame) = render(&mut EmergencyBrake, ms);
+            assert!(running);
+            let on = frame[0].r > 0;
+            flashes += (on && !was_on) as u32;
+            was_on = on;
+        }
+        assert_eq!(flashes, 5);
+        assert!(!render(&mut EmergencyBrake, 1000).0);
+    }
+
+    #[test]
+    fn sweep_only_covers_its_view() {
+        use crate::layout::{Side, TRAILER};
+
+        let left = TRAILER.segment(Side::Left).unwrap();
+        let ctx = AnimationContext::new(0.0, 12.0, SPEED, VAL_3, VAL_3, HIGHLIGHT_4, HB);
+        let mut sweep = Sweep::new(ctx).view(left.view().reverse());
+        let (_, frame) = render(&mut sweep, 0);
+        // starts at the top of the left side
+        assert_eq!(frame[left.leds.end - 1].r, u16::MAX);
+        assert_eq!(frame[left.leds.start].r, (VAL_3 * 257.0) as u16);
+        assert!(frame[left.leds.end..]
+            .iter()
+            .all(|c| *c == Color16::new(1, 1, 1)));
+    }
+}
@@ -0,0 +1,362 @@
+//! Fixed-point version of [`AnimationContext`](crate::AnimationContext).
+//!
+//! The ESP32-C3 has no FPU, so every `f32` operation in `calc_value` is a
+//! soft-float library call. [`FixedAnimationContext`] does the same math in
+//! integers and produces the same output within one LSB.
+//!
+//! Only an [`Easing`] of the motion still takes a few float operations, once
+//! per frame rather than per LED.
+//!
+//! Enable the `fixed-point` feature to have [`TrailerLight`](crate::TrailerLight)
+//! use it, `cargo bench` compares the speed of both on the host.
+
+use core::time::Duration;
+
+use crate::{color::SweepColors, easing::Easing, kernel::Kernel, Color16, NUM_LEDS};
+
+/// Fractional bits of positions. Positions are LED indices, 8 integer bits
+/// are plenty, and the fractional bits keep the rounding error of the
+/// position far below one LSB of the output.
+const FRAC_BITS: u32 = 24;
+const ONE: i32 = 1 << FRAC_BITS;
+
+/// Distances between positions have to be below this many LEDs to fit.
+const MAX_DISTANCE: f32 = (1 << (31 - FRAC_BITS)) as f32;
+
+/// Whether `pos` is close enough to every LED of the strip for positions to
+/// hold its distance to them.
+pub fn in_range(pos: f32) -> bool {
+    pos > (NUM_LEDS - 1) as f32 - MAX_DISTANCE && pos < MAX_DISTANCE
+}
+
+fn to_fixed(v: f32) -> i32 {
+    let v = v * ONE as f32;
+    (if v < 0.0 { v - 0.5 } else { v + 0.5 }) as i32
+}
+
+/// Speeds can be higher than the 128 LEDs per second positions hold, so
+/// they get the same fractional bits in 64 bits. Speeds too slow for them
+/// still move.
+fn to_fixed_speed(v: f32) -> i64 {
+    ((v as f64 * ONE as f64 + 0.5) as i64).max(1)
+}
+
+/// Converts an 8 bit brightness to the 16 bit output.
+fn to_output(v: f32) -> u32 {
+    (v * 257.0) as u32
+}
+
+pub struct FixedAnimationContext {
+    start_pos: i32,
+    end_pos: i32,
+    speed: i64, // LEDs per second, see `to_fixed_speed`
+    asc: bool,
+    bb: u32, // base brightness
+    tb: u32, // target brightness
+    hb: u32, // highlight brightness
+    hw: i32, // highlight width
+    // highlight brightness / highlight width, with 32 fractional bits
+    falloff: u64,
+    kernel: Kernel,
+    easing: Easing,
+}
+
+impl FixedAnimationContext {
+    /// Takes the same parameters as
+    /// [`AnimationContext::new`](crate::AnimationContext::new).
+    ///
+    /// # Panics
+    ///
+    /// Also if `start_pos` or `end_pos` isn't [`in_range`], or `hw` is 128
+    /// LEDs or more.
+    pub fn new(
+        start_pos: f32,
+        end_pos: f32,
+        speed: f32,
+        bb: f32,
+        tb: f32,
+        hb: f32,
+        hw: f32,
+    ) -> FixedAnimationContext {
+        assert!(bb <= tb && tb <= hb);
+        assert!(speed > 0.0);
+        assert!(in_range(start_pos) && in_range(end_pos));
+        assert!((0.0..MAX_DISTANCE).contains(&hw));
+        let hb = to_output(hb);
+        let hw = to_fixed(hw);
+        FixedAnimationContext {
+            start_pos: to_fixed(start_pos),
+            end_pos: to_fixed(end_pos),
+            speed: to_fixed_speed(speed),
+            asc: start_pos < end_pos,
+            bb: to_output(bb),
+            tb: to_output(tb),
+            hb,
+            hw,
+            falloff: if hw > 0 {
+                ((hb as u64) << 32) / hw as u64
+            } else {
+                0
+            },
+            kernel: Kernel::Triangle,
+            easing: Easing::Linear,
+        }
+    }
+
+    /// See [`AnimationContext::kernel`](crate::AnimationContext::kernel).
+    pub fn kernel(mut self, kernel: Kernel) -> Self {
+        self.kernel = kernel;
+        self
+    }
+
+    /// See [`AnimationContext::easing`](crate::AnimationContext::easing).
+    pub fn easing(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+
+    /// Time the highlight takes from `start_pos` to `end_pos`.
+    pub fn duration(&self) -> Duration {
+        let distance = (self.end_pos - self.start_pos).unsigned_abs() as u64;
+        Duration::from_micros(distance * 1_000_000 / self.speed as u64)
+    }
+
+    /// Position of the highlight `t` after the start, it stops at `end_pos`.
+    pub fn position(&self, t: Duration) -> i32 {
+        let duration = self.duration();
+        if self.easing != Easing::Linear {
+            if t >= duration {
+                return self.end_pos;
+            }
+            let progress = self
+                .easing
+                .apply(t.as_micros() as f32 / duration.as_micros() as f32);
+            return self.start_pos + ((self.end_pos - self.start_pos) as f32 * progress) as i32;
+        }
+        // past the end anyway a microsecond after the duration, which keeps
+        // the product from overflowing
+        let t = t.min(duration + Duration::from_micros(1));
+        let distance = self.speed * t.as_micros() as i64 / 1_000_000;
+        if self.asc {
+            (self.start_pos as i64 + distance).min(self.end_pos as i64) as i32
+        } else {
+            (self.start_pos as i64 - distance).max(self.end_pos as i64) as i32
+        }
+    }
+
+    /// Writes the values at `t` after the start into `v`. Returns `false`
+    /// once the highlight has reached `end_pos`.
+    pub fn render(&self, t: Duration, v: &mut [u16]) -> bool {
+        let hpos = self.position(t);
+        self.calc_values(hpos, v);
+        hpos != self.end_pos
+    }
+
+    /// Like [`render`](Self::render), but in the given colors instead of
+    /// the brightness values.
+    pub fn render_colors(&self, t: Duration, colors: &SweepColors, c: &mut [Color16]) -> bool {
+        let hpos = self.position(t);
+        for (i, c) in c.iter_mut().enumerate() {
+            let (passed, highlight) = self.calc_parts(hpos, i);
+            *c = colors.at(passed, highlight);
+        }
+        hpos != self.end_pos
+    }
+
+    pub fn calc_values(&self, hpos: i32, v: &mut [u16]) {
+        for (i, v) in v.iter_mut().enumerate() {
+            *v = self.calc_value(hpos, i) as u16;
+        }
+    }
+
+    /// Whether the highlight at `hpos` has passed the LED at index `pos`, and
+    /// how much the highlight lights it, `u16::MAX` at its center.
+    pub fn calc_parts(&self, hpos: i32, pos: usize) -> (bool, u16) {
+        let pos = pos as i32 * ONE;
+        let passed = self.asc && hpos >= pos || !self.asc && hpos <= pos;
+        let pos_diff = (hpos - pos).unsigned_abs() as i32;
+        (passed, self.highlight_weight(pos_diff))
+    }
+
+    /// Brightness of the LED at index `pos` with the highlight at `hpos`,
+    /// in 16 bit output units.
+    pub fn calc_value(&self, hpos: i32, pos: usize) -> u32 {
+        let pos = pos as i32 * ONE;
+        let use_tb = self.asc && hpos >= pos || !self.asc && hpos <= pos;
+        let ambient = if use_tb { self.tb } else { self.bb };
+
+        let pos_diff = (hpos - pos).unsigned_abs() as i32;
+        let highlight = if pos_diff >= self.hw {
+            0
+        } else if self.kernel == Kernel::Triangle {
+            ((self.falloff * (self.hw - pos_diff) as u64) >> 32) as u32
+        } else {
+            let weight = self.kernel.apply_ratio(pos_diff as u32, self.hw as u32);
+            (self.hb as u64 * weight as u64 / ((u16::MAX as u64) << 16)) as u32
+        };
+        highlight.max(ambient)
+    }
+
+    /// How much the highlight lights an LED `pos_diff` away from its center,
+    /// `u16::MAX` at the center.
+    fn highlight_weight(&self, pos_diff: i32) -> u16 {
+        (self.kernel.apply_ratio(pos_diff as u32, self.hw as u32) >> 16) as u16
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AnimationContext;
+
+    /// Runs both implementations side by side and returns the largest
+    /// difference of their output.
+    fn max_difference(params: [f32; 7]) -> u16 {
+        max_difference_with(params, Kernel::Triangle, Easing::Linear)
+    }
+
+    fn max_difference_with(params: [f32; 7], kernel: Kernel, easing: Easing) -> u16 {
+        let [start, end, speed, bb, tb, hb, hw] = params;
+        let float = AnimationContext::new(start, end, speed, bb, tb, hb, hw)
+            .kernel(kernel)
+            .easing(easing);
+        let fixed = FixedAnimationContext::new(start, end, speed, bb, tb, hb, hw)
+            .kernel(kernel)
+            .easing(easing);
+        assert!(float.duration().abs_diff(fixed.duration()) < Duration::from_micros(2));
+
+        let mut v_float = [0; 29];
+        let mut v_fixed = [0; 29];
+        let mut max = 0;
+        // odd step to not only hit round positions
+        let step = Duration::from_micros(1_237);
+        let mut t = Duration::ZERO;
+        loop {
+            let more = float.render(t, &mut v_float);
+            assert_eq!(more, fixed.render(t, &mut v_fixed));
+            for (a, b) in v_float.iter().zip(&v_fixed) {
+                max = max.max(a.abs_diff(*b));
+            }
+            if !more {
+                return max;
+            }
+            t += step;
+        }
+    }
+
+    #[test]
+    fn matches_float_within_one_lsb() {
+        for params in [
+            // the animations of `TrailerLight`
+            [-3.0, 32.0, 43.0, 0.0, 10.0, 30.0, 3.0],
+            [32.0, -3.0, 43.0, 10.0, 30.0, 60.0, 3.0],
+            [-3.0, 32.0, 43.0, 30.0, 60.0, 150.0, 3.0],
+            [-3.0, 32.0, 43.0 / 3.0, 60.0, 60.0, 255.0, 6.0],
+            // odd values
+            [0.5, 20.25, 7.3, 1.5, 7.25, 200.5, 1.3],
+            [28.0, 0.0, 91.0, 0.0, 0.0, 255.0, 10.0],
+        ] {
+            let diff = max_difference(params);
+            assert!(diff <= 1, "{:?} differs by {}", params, diff);
+        }
+    }
+
+    #[test]
+    fn kernels_and_easing_match_float() {
+        let params = [-3.0, 32.0, 43.0, 30.0, 60.0, 150.0, 3.0];
+        for kernel in [
+            Kernel::Triangle,
+            Kernel::Cosine,
+            Kernel::Gaussian,
+            Kernel::FlatTop,
+        ] {
+            for easing in [
+                Easing::Linear,
+                Easing::EaseIn,
+                Easing::EaseOut,
+                Easing::EaseInOut,
+                Easing::EaseInCubic,
+                Easing::EaseOutCubic,
+                Easing::EaseInOutCubic,
+                Easing::Bounce,
+            ] {
+                let diff = max_difference_with(params, kernel, easing);
+                assert!(diff <= 1, "{:?} {:?} differs by {}", kernel, easing, diff);
+            }
+        }
+    }
+
+    /// Before the animations were driven by time, the float version added
+    /// up a step width per frame. Fixed-point positions are exact, so they
+    /// differ from that by one LSB plus what the `f32` sum drifted off.
+    #[test]
+    fn matches_frame_stepped_float() {
+        for params in [
+            [-3.0, 32.0, 0.13, 0.0, 10.0, 30.0, 3.0],
+            [32.0, -3.0, 0.13, 10.0, 30.0, 60.0, 3.0],
+            [-3.0, 32.0, 0.13, 30.0, 60.0, 150.0, 3.0],
+            [-3.0, 32.0, 0.13 / 3.0, 60.0, 60.0, 255.0, 6.0],
+        ] {
+            let [start, end, step, bb, tb, hb, hw] = params;
+            let float = AnimationContext::new(start, end, 1.0, bb, tb, hb, hw);
+            // a step every 10 ms
+            let fixed = FixedAnimationContext::new(start, end, step * 100.0, bb, tb, hb, hw);
+            let step = if start < end { step } else { -step };
+
+            let mut v_float = [0; 29];
+            let mut v_fixed = [0; 29];
+            let (mut max, mut drift) = (0, 0.0_f64);
+            let mut pos = start;
+            let mut frame = 0;
+            while (pos - start).abs() <= (end - start).abs() {
+                float.calc_values(pos, &mut v_float);
+                let t = Duration::from_millis(frame * 10);
+                fixed.calc_values(fixed.position(t), &mut v_fixed);
+                for (a, b) in v_float.iter().zip(&v_fixed) {
+                    max = max.max(a.abs_diff(*b));
+                }
+                let exact = start as f64 + frame as f64 * step as f64;
+                drift = drift.max((pos as f64 - exact).abs());
+                pos += step;
+                frame += 1;
+            }
+            let drift = (drift * hb as f64 * 257.0 / hw as f64).ceil() as u16;
+            assert!(max <= 1 + drift, "{:?} differs by {}", params, max);
+        }
+    }
+
+    #[test]
+    fn fast_sweeps_match_float() {
+        // faster than positions can hold, up to what the VM can express
+        for speed in [200.0, 655.35] {
+            let diff = max_difference([-3.0, 32.0, speed, 0.0, 10.0, 255.0, 3.0]);
+            assert!(diff <= 1, "{} differs by {}", speed, diff);
+        }
+    }
+
+    #[test]
+    fn tiny_speeds_still_move() {
+        let ctx = FixedAnimationContext::new(0.0, 1.0, 1e-9, 0.0, 10.0, 100.0, 1.0);
+        assert_eq!(ctx.duration(), Duration::from_secs(1 << 24));
+        assert!(ctx.position(Duration::from_secs(3600)) > 0);
+    }
+
+    #[test]
+    fn positions_in_range() {
+        assert!(in_range(-60.0) && in_range(127.0));
+        assert!(!in_range(-100.0) && !in_range(128.0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn positions_out_of_range_are_rejected() {
+        FixedAnimationContext::new(-100.0, 10.0, 1.0, 5.0, 10.0, 100.0, 1.0);
+    }
+
+    #[test]
+    fn zero_width_highlight() {
+        let ctx = FixedAnimationContext::new(0.0, 10.0, 1.0, 5.0, 10.0, 100.0, 0.0);
+        assert_eq!(ctx.calc_value(to_fixed(4.0), 4), 10 * 257);
+        assert_eq!(ctx.calc_value(to_fixed(4.0), 5), 5 * 257);
+    }
+}
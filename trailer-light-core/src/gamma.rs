@@ -0,0 +1,226 @@
+//! Perceptual brightness correction.
+//!
+//! The LEDs' light output is linear in the PWM value, but our eyes don't
+//! perceive it that way: the steps between low values look much bigger than
+//! between high ones. A [`Gamma`] maps perceptual brightness, as written by
+//! the animations, to the PWM value sent to the LEDs.
+//!
+//! Both are 16 bit values, the table has 256 entries which are interpolated
+//! linearly. This keeps the detail at the dark end of the curves, where
+//! several input steps map to less than one 8 bit output step.
+//!
+//! The lookup tables are computed by `const fn`s, so a table stored in a
+//! `const` or `static` is generated at compile time:
+//!
+//! ```
+//! use trailer_light_core::gamma::Gamma;
+//!
+//! static GAMMA_2_5: Gamma = Gamma::power(2.5);
+//! ```
+
+use crate::Color16;
+
+/// Lookup table from perceptual brightness to PWM value.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Gamma {
+    /// Output for the inputs `i * 257`.
+    table: [u16; 256],
+}
+
+/// No correction, values are sent to the LEDs as they are.
+pub static LINEAR: Gamma = Gamma::power(1.0);
+
+/// The classic gamma 2.2 curve.
+pub static GAMMA_2_2: Gamma = Gamma::power(2.2);
+
+/// CIE 1931 lightness (L*), the most even steps for the eye.
+pub static CIE_LIGHTNESS: Gamma = Gamma::cie_lightness();
+
+impl Gamma {
+    /// Power-law curve, `out = in ^ gamma`.
+    pub const fn power(gamma: f64) -> Gamma {
+        assert!(gamma > 0.0);
+        let mut table = [0; 256];
+        let mut i = 0;
+        while i < 256 {
+            table[i] = to_u16(powf(i as f64 / 255.0, gamma));
+            i += 1;
+        }
+        Gamma { table }
+    }
+
+    /// Inverse of the CIE 1931 lightness function, the input is L* scaled
+    /// to the full range.
+    pub const fn cie_lightness() -> Gamma {
+        let mut table = [0; 256];
+        let mut i = 0;
+        while i < 256 {
+            let l = i as f64 * 100.0 / 255.0;
+            let y = if l > 8.0 {
+                let f = (l + 16.0) / 116.0;
+                f * f * f
+            } else {
+                l / 903.3
+            };
+            table[i] = to_u16(y);
+            i += 1;
+        }
+        Gamma { table }
+    }
+
+    /// Builds a curve from a custom table, entry `i` is the output for the
+    /// 8 bit input `i`.
+    pub const fn from_table(table: [u16; 256]) -> Gamma {
+        Gamma { table }
+    }
+
+    pub fn table(&self) -> &[u16; 256] {
+        &self.table
+    }
+
+    pub fn correct_value(&self, v: u16) -> u16 {
+        // position in the table in 8.8 fixed point
+        let pos = v as u32 * 255 * 256 / u16::MAX as u32;
+        let i = (pos >> 8) as usize;
+        let frac = pos & 0xff;
+        if i == 255 {
+            return self.table[255];
+        }
+        let (a, b) = (self.table[i] as i32, self.table[i + 1] as i32);
+        (a + (b - a) * frac as i32 / 256) as u16
+    }
+
+    pub fn correct(&self, c: Color16) -> Color16 {
+        Color16::new(
+            self.correct_value(c.r),
+            self.correct_value(c.g),
+            self.correct_value(c.b),
+        )
+    }
+}
+
+/// Rounds `v` in `0.0..=1.0` to `0..=65535`.
+const fn to_u16(v: f64) -> u16 {
+    (v * 65535.0 + 0.5) as u16
+}
+
+// `f64::powf` and friends aren't available in `core`, let alone in const
+// context. These series are plenty accurate for 16 bit tables.
+
+const LN_2: f64 = core::f64::consts::LN_2;
+
+/// `x ^ y` for `x >= 0`.
+const fn powf(x: f64, y: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    exp(y * ln(x))
+}
+
+/// Natural logarithm for `x > 0`.
+const fn ln(mut x: f64) -> f64 {
+    // x = m * 2^k with m in [1, 2)
+    let mut k = 0;
+    while x >= 2.0 {
+        x /= 2.0;
+        k += 1;
+    }
+    while x < 1.0 {
+        x *= 2.0;
+        k -= 1;
+    }
+    // ln(m) = 2 * atanh(z) with z = (m - 1) / (m + 1) <= 1/3
+    let z = (x - 1.0) / (x + 1.0);
+    let z2 = z * z;
+    let mut term = z;
+    let mut sum = 0.0;
+    let mut n = 0;
+    while n < 20 {
+        sum += term / (2 * n + 1) as f64;
+        term *= z2;
+        n += 1;
+    }
+    2.0 * sum + k as f64 * LN_2
+}
+
+const fn exp(x: f64) -> f64 {
+    // x = k * ln(2) + r with |r| <= ln(2) / 2
+    let k = (x / LN_2 + if x < 0.0 { -0.5 } else { 0.5 }) as i32;
+    let r = x - k as f64 * LN_2;
+    let mut term = 1.0;
+    let mut sum = 1.0;
+    let mut n = 1;
+    while n < 20 {
+        term *= r / n as f64;
+        sum += term;
+        n += 1;
+    }
+    let mut i = 0;
+    while i < k {
+        sum *= 2.0;
+        i += 1;
+    }
+    while i > k {
+        sum /= 2.0;
+        i -= 1;
+    }
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn linear_is_identity() {
+        for v in (0..=255).map(|v| v * 257) {
+            assert_eq!(LINEAR.correct_value(v), v);
+        }
+        assert!((0..=u16::MAX).all(|v| LINEAR.correct_value(v).abs_diff(v) <= 1));
+    }
+
+    #[test]
+    fn curves_keep_the_endpoints() {
+        for gamma in [&GAMMA_2_2, &CIE_LIGHTNESS] {
+            assert_eq!(gamma.correct_value(0), 0);
+            assert_eq!(gamma.correct_value(u16::MAX), u16::MAX);
+        }
+    }
+
+    #[test]
+    fn curves_are_monotonic() {
+        for gamma in [&GAMMA_2_2, &CIE_LIGHTNESS, &Gamma::power(0.5)] {
+            let mut prev = 0;
+            for v in 0..=u16::MAX {
+                let out = gamma.correct_value(v);
+                assert!(out >= prev);
+                prev = out;
+            }
+        }
+    }
+
+    #[test]
+    fn matches_std() {
+        for g in [0.5, 1.8, 2.2, 2.8] {
+            let gamma = Gamma::power(g);
+            for i in 0..=255u16 {
+                let expected = ((i as f64 / 255.0).powf(g) * 65535.0).round() as u16;
+                assert_eq!(gamma.correct_value(i * 257), expected, "gamma {} at {}", g, i);
+            }
+        }
+    }
+
+    #[test]
+    fn interpolates_between_entries() {
+        let v = GAMMA_2_2.correct_value(10 * 257 + 128);
+        assert!(v > GAMMA_2_2.correct_value(10 * 257));
+        assert!(v < GAMMA_2_2.correct_value(11 * 257));
+    }
+
+    #[test]
+    fn cie_lightness_midpoint() {
+        // L* = 50 is 18.4% luminance
+        let v = CIE_LIGHTNESS.correct_value(u16::MAX / 2);
+        assert!((12000..12150).contains(&v), "{}", v);
+    }
+}
@@ -0,0 +1,223 @@
+//! Accelerometers.
+//!
+//! The brake detection only needs the [`Accelerometer`] trait, so it works
+//! with any part and with recorded data. [`Mpu6050`] implements it for the
+//! MPU-6050 on the I2C bus of `embedded-hal`.
+//!
+//! Accelerations are in milli-g, in the axes of the sensor. At rest the
+//! sensor reads 1 g pointing up, against gravity.
+
+use embedded_hal::blocking::i2c::{Write, WriteRead};
+
+/// Acceleration in milli-g.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Accel {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl Accel {
+    pub const fn new(x: i32, y: i32, z: i32) -> Self {
+        Accel { x, y, z }
+    }
+
+    pub fn dot(self, other: Accel) -> i64 {
+        self.x as i64 * other.x as i64
+            + self.y as i64 * other.y as i64
+            + self.z as i64 * other.z as i64
+    }
+
+    /// Length of the vector, rounded down.
+    pub fn magnitude(self) -> i32 {
+        (self.dot(self) as u64).isqrt() as i32
+    }
+}
+
+pub trait Accelerometer {
+    type Error;
+
+    /// Reads the current acceleration.
+    fn accel(&mut self) -> Result<Accel, Self::Error>;
+}
+
+/// Full scale of the measurements. Smaller ranges are more precise.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Range {
+    #[default]
+    G2,
+    G4,
+    G8,
+    G16,
+}
+
+impl Range {
+    fn bits(self) -> u8 {
+        self as u8
+    }
+
+    /// Counts of the raw values per g.
+    fn counts_per_g(self) -> i32 {
+        16_384 >> self.bits()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error<E> {
+    I2c(E),
+    /// The device at the address doesn't identify as the expected part, the
+    /// value read from its `WHO_AM_I` register.
+    UnknownDevice(u8),
+}
+
+const DLPF_CONFIG: u8 = 0x1a;
+const ACCEL_CONFIG: u8 = 0x1c;
+const ACCEL_XOUT_H: u8 = 0x3b;
+const PWR_MGMT_1: u8 = 0x6b;
+const WHO_AM_I: u8 = 0x75;
+
+/// InvenSense MPU-6050, only its accelerometer.
+pub struct Mpu6050<I2C> {
+    i2c: I2C,
+    address: u8,
+    range: Range,
+}
+
+impl<I2C, E> Mpu6050<I2C>
+where
+    I2C: Write<Error = E> + WriteRead<Error = E>,
+{
+    /// Address with the AD0 pin low, it's `0x69` with AD0 high.
+    pub const ADDRESS: u8 = 0x68;
+
+    /// Checks the device at `address`, wakes it up and configures the
+    /// [`Range::G2`] range and its low-pass filter at 44 Hz.
+    pub fn new(i2c: I2C, address: u8) -> Result<Self, Error<E>> {
+        let mut mpu = Mpu6050 {
+            i2c,
+            address,
+            range: Range::G2,
+        };
+        let mut id = [0];
+        mpu.i2c
+            .write_read(address, &[WHO_AM_I], &mut id)
+            .map_err(Error::I2c)?;
+        // bits 6..1 hold the default address
+        if id[0] & 0x7e != Self::ADDRESS {
+            return Err(Error::UnknownDevice(id[0]));
+        }
+        mpu.write_register(PWR_MGMT_1, 0)?;
+        mpu.write_register(DLPF_CONFIG, 3)?;
+        mpu.set_range(Range::G2)?;
+        Ok(mpu)
+    }
+
+    pub fn set_range(&mut self, range: Range) -> Result<(), Error<E>> {
+        self.write_register(ACCEL_CONFIG, range.bits() << 3)?;
+        self.range = range;
+        Ok(())
+    }
+
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+
+    fn write_register(&mut self, register: u8, value: u8) -> Result<(), Error<E>> {
+        self.i2c
+            .write(self.address, &[register, value])
+            .map_err(Error::I2c)
+    }
+}
+
+impl<I2C, E> Accelerometer for Mpu6050<I2C>
+where
+    I2C: Write<Error = E> + WriteRead<Error = E>,
+{
+    type Error = Error<E>;
+
+    fn accel(&mut self) -> Result<Accel, Self::Error> {
+        let mut raw = [0; 6];
+        self.i2c
+            .write_read(self.address, &[ACCEL_XOUT_H], &mut raw)
+            .map_err(Error::I2c)?;
+        let counts = self.range.counts_per_g();
+        let axis = |i: usize| i16::from_be_bytes([raw[i], raw[i + 1]]) as i32 * 1000 / counts;
+        Ok(Accel::new(axis(0), axis(2), axis(4)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Register file of an MPU-6050.
+    struct Registers([u8; 128]);
+
+    impl Write for Registers {
+        type Error = ();
+
+        fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), ()> {
+            assert_eq!(address, Mpu6050::<Registers>::ADDRESS);
+            self.0[bytes[0] as usize..][..bytes.len() - 1].copy_from_slice(&bytes[1..]);
+            Ok(())
+        }
+    }
+
+    impl WriteRead for Registers {
+        type Error = ();
+
+        fn write_read(&mut self, address: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), ()> {
+            if address != Mpu6050::<Registers>::ADDRESS {
+                return Err(());
+            }
+            buffer.copy_from_slice(&self.0[bytes[0] as usize..][..buffer.len()]);
+            Ok(())
+        }
+    }
+
+    fn registers() -> Registers {
+        let mut registers = [0; 128];
+        registers[WHO_AM_I as usize] = 0x68;
+        // asleep after reset
+        registers[PWR_MGMT_1 as usize] = 0x40;
+        Registers(registers)
+    }
+
+    #[test]
+    fn wakes_up_and_configures() {
+        let mpu = Mpu6050::new(registers(), Mpu6050::<Registers>::ADDRESS).unwrap();
+        let configured = mpu.release().0;
+        assert_eq!(configured[PWR_MGMT_1 as usize], 0);
+        assert_eq!(configured[DLPF_CONFIG as usize], 3);
+        assert_eq!(configured[ACCEL_CONFIG as usize], 0);
+
+        assert_eq!(Mpu6050::new(registers(), 0x69).err(), Some(Error::I2c(())));
+        let mut other = registers();
+        other.0[WHO_AM_I as usize] = 0x71;
+        assert_eq!(
+            Mpu6050::new(other, Mpu6050::<Registers>::ADDRESS).err(),
+            Some(Error::UnknownDevice(0x71))
+        );
+    }
+
+    #[test]
+    fn reads_milli_g() {
+        let mut registers = registers();
+        let raw = [16_384i16, -8_192, 100];
+        for (i, v) in raw.iter().enumerate() {
+            registers.0[ACCEL_XOUT_H as usize + 2 * i..][..2].copy_from_slice(&v.to_be_bytes());
+        }
+        let mut mpu = Mpu6050::new(registers, Mpu6050::<Registers>::ADDRESS).unwrap();
+        assert_eq!(mpu.accel(), Ok(Accel::new(1000, -500, 6)));
+
+        mpu.set_range(Range::G8).unwrap();
+        assert_eq!(mpu.accel(), Ok(Accel::new(4000, -2000, 24)));
+        assert_eq!(mpu.release().0[ACCEL_CONFIG as usize], 0x10);
+    }
+
+    #[test]
+    fn magnitude() {
+        assert_eq!(Accel::new(0, 0, 1000).magnitude(), 1000);
+        assert_eq!(Accel::new(300, -400, 0).magnitude(), 500);
+    }
+}
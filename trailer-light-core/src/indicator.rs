@@ -0,0 +1,221 @@
+//! Turn indicators on the side segments.
+//!
+//! [`Indicators`] wraps whatever [`Player`] drives the light and, while an
+//! indicator is on, flashes the matching side of [`TRAILER`] amber on top of
+//! it. The rest of the strip keeps playing.
+
+use core::time::Duration;
+
+use crate::{
+    effect::{Frame, Player},
+    layout::{Side, TRAILER},
+    time::Instant,
+    widen, Color, Color16,
+};
+
+/// Color of the flashes.
+pub const AMBER: Color = Color::new(255, 100, 0);
+
+/// One flash and the pause after it, about 1.5 Hz.
+pub const PERIOD: Duration = Duration::from_millis(666);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Indicator {
+    Left,
+    Right,
+    /// Both sides at once.
+    Hazard,
+}
+
+impl Indicator {
+    fn flashes(self, side: Side) -> bool {
+        matches!(
+            (self, side),
+            (Indicator::Left, Side::Left)
+                | (Indicator::Right, Side::Right)
+                | (Indicator::Hazard, _)
+        )
+    }
+}
+
+/// Plays `P` with turn indicators on top.
+pub struct Indicators<P> {
+    player: P,
+    active: Option<(Indicator, Instant)>,
+    timeout: Option<Duration>,
+}
+
+impl<P: Player> Indicators<P> {
+    pub fn new(player: P) -> Self {
+        Indicators {
+            player,
+            active: None,
+            timeout: None,
+        }
+    }
+
+    /// Turns indicators off by themselves once they've been on for
+    /// `timeout`, e.g. in case the switch isn't reset after a turn.
+    pub fn auto_cancel(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Starts flashing at `now`, replacing the indicator that was on.
+    /// Starting the one that is already on doesn't interrupt its rhythm.
+    pub fn start(&mut self, indicator: Indicator, now: Instant) {
+        if self.active.map(|(i, _)| i) != Some(indicator) {
+            self.active = Some((indicator, now));
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.active = None;
+    }
+
+    /// The indicator that is on, if any.
+    pub fn active(&self) -> Option<Indicator> {
+        self.active.map(|(i, _)| i)
+    }
+
+    pub fn player(&mut self) -> &mut P {
+        &mut self.player
+    }
+
+    pub fn into_player(self) -> P {
+        self.player
+    }
+}
+
+impl<P: Player> Player for Indicators<P> {
+    /// Renders the player, then the indicator on top. Returns `false` once
+    /// the player has finished and no indicator is on.
+    fn render(&mut self, now: Instant, frame: &mut Frame) -> bool {
+        let running = self.player.render(now, frame);
+        let Some((indicator, start)) = self.active else {
+            return running;
+        };
+        let elapsed = now - start;
+        if self.timeout.is_some_and(|timeout| elapsed >= timeout) {
+            self.active = None;
+            return running;
+        }
+
+        // on for the first half of every period
+        let on = elapsed.as_micros() % PERIOD.as_micros() < PERIOD.as_micros() / 2;
+        let color = if on {
+            widen(AMBER)
+        } else {
+            Color16::new(0, 0, 0)
+        };
+        for segment in TRAILER.segments() {
+            if indicator.flashes(segment.side) {
+                frame[segment.leds.clone()].fill(color);
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NUM_LEDS;
+
+    /// Solid blue, finished after a second.
+    struct Blue;
+
+    impl Player for Blue {
+        fn render(&mut self, now: Instant, frame: &mut Frame) -> bool {
+            *frame = [Color16::new(0, 0, 1000); NUM_LEDS];
+            now < at_ms(1000)
+        }
+    }
+
+    fn at_ms(ms: u64) -> Instant {
+        Instant::from_micros(ms * 1_000)
+    }
+
+    fn render(indicators: &mut Indicators<Blue>, ms: u64) -> (bool, Frame) {
+        let mut frame = [Color16::default(); NUM_LEDS];
+        let running = indicators.render(at_ms(ms), &mut frame);
+        (running, frame)
+    }
+
+    fn side(frame: &Frame, side: Side) -> &[Color16] {
+        &frame[TRAILER.segment(side).unwrap().leds.clone()]
+    }
+
+    #[test]
+    fn flashes_its_side_at_one_and_a_half_hertz() {
+        let amber = widen(AMBER);
+        let blue = Color16::new(0, 0, 1000);
+        let mut indicators = Indicators::new(Blue);
+        indicators.start(Indicator::Left, at_ms(100));
+
+        let mut flashes = 0;
+        let mut was_on = false;
+        for ms in 100..3100 {
+            let (_, frame) = render(&mut indicators, ms);
+            let on = side(&frame, Side::Left)[0] == amber;
+            assert!(on || side(&frame, Side::Left)[0] == Color16::new(0, 0, 0));
+            assert!(side(&frame, Side::Left)
+                .iter()
+                .all(|c| *c == side(&frame, Side::Left)[0]));
+            assert!(side(&frame, Side::Top).iter().all(|c| *c == blue));
+            assert!(side(&frame, Side::Right).iter().all(|c| *c == blue));
+            flashes += (on && !was_on) as u32;
+            was_on = on;
+        }
+        // 3 s at 1.5 Hz
+        assert_eq!(flashes, 5);
+        assert_eq!(render(&mut indicators, 100).1[0], amber);
+        assert_eq!(
+            render(&mut indicators, 100 + 333).1[0],
+            Color16::new(0, 0, 0)
+        );
+    }
+
+    #[test]
+    fn hazard_flashes_both_sides() {
+        let mut indicators = Indicators::new(Blue);
+        indicators.start(Indicator::Hazard, at_ms(0));
+        let (_, frame) = render(&mut indicators, 10);
+        assert!(side(&frame, Side::Left).iter().all(|c| *c == widen(AMBER)));
+        assert!(side(&frame, Side::Right).iter().all(|c| *c == widen(AMBER)));
+    }
+
+    #[test]
+    fn restarting_keeps_the_rhythm_switching_restarts_it() {
+        let mut indicators = Indicators::new(Blue);
+        indicators.start(Indicator::Right, at_ms(0));
+        indicators.start(Indicator::Right, at_ms(400));
+        assert_eq!(
+            render(&mut indicators, 400).1[NUM_LEDS - 1],
+            Color16::new(0, 0, 0)
+        );
+
+        indicators.start(Indicator::Left, at_ms(400));
+        let (_, frame) = render(&mut indicators, 400);
+        assert_eq!(frame[0], widen(AMBER));
+        assert_eq!(frame[NUM_LEDS - 1], Color16::new(0, 0, 1000));
+    }
+
+    #[test]
+    fn stops_and_cancels() {
+        let mut indicators = Indicators::new(Blue).auto_cancel(Duration::from_secs(30));
+        indicators.start(Indicator::Left, at_ms(0));
+        // keeps running after the player has finished
+        assert!(render(&mut indicators, 2000).0);
+        indicators.stop();
+        assert!(!render(&mut indicators, 2000).0);
+
+        indicators.start(Indicator::Left, at_ms(3000));
+        assert!(render(&mut indicators, 32_999).0);
+        assert_eq!(indicators.active(), Some(Indicator::Left));
+        let (running, frame) = render(&mut indicators, 33_000);
+        assert!(!running);
+        assert_eq!(indicators.active(), None);
+        assert_eq!(frame[0], Color16::new(0, 0, 1000));
+    }
+}
@@ -0,0 +1,207 @@
+//! Shapes of the highlight of a sweep.
+//!
+//! A kernel gives the brightness of the highlight by the distance to its
+//! center, relative to the highlight width: 1 at the center, 0 at the width
+//! and beyond. Except for the triangle, the curves are tabulated at compile
+//! time, so neither `cos` nor `exp` are needed on the target and the
+//! [fixed-point](crate::fixed) context can use them without floats.
+
+/// Falloff of the highlight brightness.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Kernel {
+    /// Linear falloff, a tent.
+    #[default]
+    Triangle,
+    /// Half a cosine period, round at the center and soft at the edges.
+    Cosine,
+    /// Bell curve cut off at three standard deviations, narrower and
+    /// brighter in the center than the others.
+    Gaussian,
+    /// Full brightness over half the width, then a soft cosine edge.
+    FlatTop,
+}
+
+/// Number of segments of the tables.
+const STEPS: usize = 64;
+
+impl Kernel {
+    /// Brightness at the relative distance `d` from the center, both in
+    /// `0.0..=1.0`.
+    pub fn apply(self, d: f32) -> f32 {
+        let d = if d < 0.0 { -d } else { d };
+        if d >= 1.0 {
+            return 0.0;
+        }
+        match self.table() {
+            None => 1.0 - d,
+            Some(table) => {
+                let x = d * STEPS as f32;
+                let i = x as usize;
+                let (a, b) = (table[i] as f32, table[i + 1] as f32);
+                (a + (b - a) * (x - i as f32)) / u16::MAX as f32
+            }
+        }
+    }
+
+    /// Like [`apply`](Self::apply) in 16 bit fixed point, `d` and the result
+    /// going from 0 to `u16::MAX` for 1.
+    pub fn apply_fixed(self, d: u16) -> u16 {
+        (self.apply_ratio(d as u32, u16::MAX as u32) >> 16) as u16
+    }
+
+    /// Brightness at the distance `d` from the center of a highlight `width`
+    /// wide, from 0 to `u16::MAX`, with 16 more fractional bits. Exact up to
+    /// the rounding of the tables.
+    pub fn apply_ratio(self, d: u32, width: u32) -> u32 {
+        if d >= width {
+            return 0;
+        }
+        let (d, width) = (d as u64, width as u64);
+        let full = (u16::MAX as u64) << 16;
+        match self.table() {
+            None => ((width - d) * full / width) as u32,
+            Some(table) => {
+                let x = d * STEPS as u64;
+                let i = (x / width) as usize;
+                let frac = ((x % width) << 16) / width;
+                let (a, b) = (table[i] as i64, table[i + 1] as i64);
+                ((a << 16) + (b - a) * frac as i64) as u32
+            }
+        }
+    }
+
+    fn table(self) -> Option<&'static [u16; STEPS + 1]> {
+        match self {
+            Kernel::Triangle => None,
+            Kernel::Cosine => Some(&COSINE),
+            Kernel::Gaussian => Some(&GAUSSIAN),
+            Kernel::FlatTop => Some(&FLAT_TOP),
+        }
+    }
+}
+
+const PI: f64 = core::f64::consts::PI;
+
+/// `cos(x)` for `x` in `0.0..=PI`, by its Taylor series.
+const fn cos(x: f64) -> f64 {
+    let (mut sum, mut term, mut n) = (0.0, 1.0, 0);
+    while n < 20 {
+        sum += term;
+        term *= -x * x / ((2 * n + 1) * (2 * n + 2)) as f64;
+        n += 1;
+    }
+    sum
+}
+
+/// `exp(-x)` for `x` in `0.0..=4.5`, by its Taylor series.
+const fn exp_neg(x: f64) -> f64 {
+    let (mut sum, mut term, mut n) = (0.0, 1.0, 0);
+    while n < 40 {
+        sum += term;
+        term *= -x / (n + 1) as f64;
+        n += 1;
+    }
+    sum
+}
+
+const fn cosine(d: f64) -> f64 {
+    0.5 * (1.0 + cos(PI * d))
+}
+
+const fn gaussian(d: f64) -> f64 {
+    // three standard deviations at d = 1, moved down to end at 0
+    let edge = exp_neg(4.5);
+    (exp_neg(4.5 * d * d) - edge) / (1.0 - edge)
+}
+
+const fn flat_top(d: f64) -> f64 {
+    if d <= 0.5 {
+        1.0
+    } else {
+        cosine((d - 0.5) * 2.0)
+    }
+}
+
+const fn tabulate(kernel: Kernel) -> [u16; STEPS + 1] {
+    let mut table = [0; STEPS + 1];
+    let mut i = 0;
+    while i <= STEPS {
+        let d = i as f64 / STEPS as f64;
+        let v = match kernel {
+            Kernel::Triangle => 1.0 - d,
+            Kernel::Cosine => cosine(d),
+            Kernel::Gaussian => gaussian(d),
+            Kernel::FlatTop => flat_top(d),
+        };
+        let v = v * u16::MAX as f64 + 0.5;
+        table[i] = if v < 0.0 {
+            0
+        } else if v > u16::MAX as f64 {
+            u16::MAX
+        } else {
+            v as u16
+        };
+        i += 1;
+    }
+    table
+}
+
+static COSINE: [u16; STEPS + 1] = tabulate(Kernel::Cosine);
+static GAUSSIAN: [u16; STEPS + 1] = tabulate(Kernel::Gaussian);
+static FLAT_TOP: [u16; STEPS + 1] = tabulate(Kernel::FlatTop);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [Kernel; 4] = [
+        Kernel::Triangle,
+        Kernel::Cosine,
+        Kernel::Gaussian,
+        Kernel::FlatTop,
+    ];
+
+    #[test]
+    fn full_at_the_center_and_off_at_the_edge() {
+        for kernel in ALL {
+            assert_eq!(kernel.apply(0.0), 1.0, "{:?}", kernel);
+            assert_eq!(kernel.apply(1.0), 0.0, "{:?}", kernel);
+            assert_eq!(kernel.apply(-0.5), kernel.apply(0.5), "{:?}", kernel);
+            assert_eq!(kernel.apply_fixed(0), u16::MAX, "{:?}", kernel);
+            assert_eq!(kernel.apply_fixed(u16::MAX), 0, "{:?}", kernel);
+        }
+    }
+
+    #[test]
+    fn falls_off_monotonically() {
+        for kernel in ALL {
+            let mut last = 1.0;
+            for i in 0..=100 {
+                let v = kernel.apply(i as f32 / 100.0);
+                assert!(v <= last, "{:?} at {}", kernel, i);
+                last = v;
+            }
+        }
+    }
+
+    #[test]
+    fn shapes() {
+        assert_eq!(Kernel::Triangle.apply(0.25), 0.75);
+        assert!((Kernel::Cosine.apply(0.5) - 0.5).abs() < 1e-4);
+        assert!((Kernel::Gaussian.apply(0.5) - 0.3171).abs() < 1e-3);
+        assert_eq!(Kernel::FlatTop.apply(0.5), 1.0);
+        assert!((Kernel::FlatTop.apply(0.75) - 0.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn fixed_matches_float() {
+        for kernel in ALL {
+            for i in 0..=1000 {
+                let d = i as f32 / 1000.0;
+                let float = kernel.apply(d) * u16::MAX as f32;
+                let fixed = kernel.apply_fixed((d * u16::MAX as f32) as u16);
+                assert!((float - fixed as f32).abs() < 64.0, "{:?} at {}", kernel, d);
+            }
+        }
+    }
+}
@@ -0,0 +1,306 @@
+//! Where the LEDs are.
+//!
+//! The strip runs around the back of the trailer: up the left side, across
+//! the top and down the right side, see [`TRAILER`]. Effects don't need to
+//! know these indices, they render into a [`View`], a virtual strip mapped
+//! onto some of the LEDs, possibly mirrored, reversed or shifted. Writing a
+//! view into a [`Frame`] puts the values where they belong physically.
+
+use core::ops::Range;
+
+use crate::{effect::Frame, Color16, NUM_LEDS};
+
+/// Position on the back of the trailer, in LED spacings from the bottom
+/// left corner.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Point {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Point {
+    pub const fn new(x: f32, y: f32) -> Self {
+        Point { x, y }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Top,
+    Right,
+}
+
+/// Direction the LED indices of a segment run in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Up,
+    Down,
+    LeftToRight,
+    RightToLeft,
+}
+
+/// A straight part of the strip.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Segment {
+    pub side: Side,
+    pub leds: Range<usize>,
+    pub direction: Direction,
+    /// Position of the first LED.
+    pub start: Point,
+}
+
+impl Segment {
+    /// Position of the LED with the index `led` on the strip.
+    pub fn position(&self, led: usize) -> Option<Point> {
+        if !self.leds.contains(&led) {
+            return None;
+        }
+        let d = (led - self.leds.start) as f32;
+        let Point { x, y } = self.start;
+        Some(match self.direction {
+            Direction::Up => Point::new(x, y + d),
+            Direction::Down => Point::new(x, y - d),
+            Direction::LeftToRight => Point::new(x + d, y),
+            Direction::RightToLeft => Point::new(x - d, y),
+        })
+    }
+
+    /// The segment as a virtual strip, in the direction of the indices.
+    pub fn view(&self) -> View {
+        View::new(self.leds.clone())
+    }
+}
+
+/// The segments of a strip.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Layout<'a> {
+    segments: &'a [Segment],
+}
+
+impl<'a> Layout<'a> {
+    pub const fn new(segments: &'a [Segment]) -> Self {
+        Layout { segments }
+    }
+
+    pub fn segments(&self) -> &'a [Segment] {
+        self.segments
+    }
+
+    pub fn segment(&self, side: Side) -> Option<&'a Segment> {
+        self.segments.iter().find(|s| s.side == side)
+    }
+
+    /// Position of the LED with the index `led`.
+    pub fn position(&self, led: usize) -> Option<Point> {
+        self.segments.iter().find_map(|s| s.position(led))
+    }
+}
+
+/// LEDs of the strip running up or down each side of the trailer, as it's
+/// mounted; [`NUM_LEDS`] counts the whole strip, the rest of it runs across
+/// the top. Has to be changed with the mounting, it can't be derived.
+const SIDE_LEDS: usize = 12;
+
+// both sides and at least one LED on top
+const _: () = assert!(2 * SIDE_LEDS < NUM_LEDS);
+
+/// The strip of the trailer light: 12 LEDs up the left side, 34 across the
+/// top, 12 down the right side.
+pub static TRAILER: Layout = Layout::new(&[
+    Segment {
+        side: Side::Left,
+        leds: 0..SIDE_LEDS,
+        direction: Direction::Up,
+        start: Point::new(0.0, 0.0),
+    },
+    Segment {
+        side: Side::Top,
+        leds: SIDE_LEDS..NUM_LEDS - SIDE_LEDS,
+        direction: Direction::LeftToRight,
+        start: Point::new(1.0, SIDE_LEDS as f32),
+    },
+    Segment {
+        side: Side::Right,
+        leds: NUM_LEDS - SIDE_LEDS..NUM_LEDS,
+        direction: Direction::Down,
+        start: Point::new(
+            (NUM_LEDS - 2 * SIDE_LEDS + 1) as f32,
+            (SIDE_LEDS - 1) as f32,
+        ),
+    },
+]);
+
+/// A virtual strip on some of the LEDs.
+///
+/// Transforms apply in the order offset, reverse, mirror, whichever order
+/// they're set in.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct View {
+    leds: Range<usize>,
+    reverse: bool,
+    mirror: bool,
+    offset: usize,
+}
+
+impl View {
+    /// The LEDs `leds` in the order of their indices.
+    pub const fn new(leds: Range<usize>) -> Self {
+        View {
+            leds,
+            reverse: false,
+            mirror: false,
+            offset: 0,
+        }
+    }
+
+    /// The whole strip.
+    pub const fn strip() -> Self {
+        View::new(0..NUM_LEDS)
+    }
+
+    /// Runs from the other end.
+    pub const fn reverse(mut self) -> Self {
+        self.reverse = !self.reverse;
+        self
+    }
+
+    /// Half as long, starting in the middle and shown on both halves.
+    pub const fn mirror(mut self) -> Self {
+        self.mirror = true;
+        self
+    }
+
+    /// Shifted by `offset` LEDs, wrapping around at the end.
+    pub const fn offset(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Number of virtual LEDs.
+    pub fn len(&self) -> usize {
+        let n = self.leds.len();
+        if self.mirror {
+            n.div_ceil(2)
+        } else {
+            n
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leds.is_empty()
+    }
+
+    /// The LEDs showing the virtual LED `i`, the second one only if mirrored.
+    pub fn leds(&self, i: usize) -> (usize, Option<usize>) {
+        let len = self.len();
+        let mut i = (i + self.offset) % len;
+        if self.reverse {
+            i = len - 1 - i;
+        }
+        if !self.mirror {
+            return (self.leds.start + i, None);
+        }
+        let n = self.leds.len();
+        let center = self.leds.start + n / 2;
+        let other = match n % 2 {
+            // odd lengths share the center LED
+            1 if i == 0 => None,
+            1 => Some(center - i),
+            _ => Some(center - 1 - i),
+        };
+        (center + i, other)
+    }
+
+    /// Writes `values` to the LEDs of the view, starting at virtual LED 0.
+    pub fn write(&self, values: &[Color16], frame: &mut Frame) {
+        for (i, &c) in values.iter().take(self.len()).enumerate() {
+            let (a, b) = self.leds(i);
+            frame[a] = c;
+            if let Some(b) = b {
+                frame[b] = c;
+            }
+        }
+    }
+
+    /// Sets the virtual LEDs `range` to `color`.
+    pub fn fill(&self, range: Range<usize>, color: Color16, frame: &mut Frame) {
+        for i in range.start..range.end.min(self.len()) {
+            let (a, b) = self.leds(i);
+            frame[a] = color;
+            if let Some(b) = b {
+                frame[b] = color;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lit(view: &View) -> Vec<usize> {
+        let mut frame = [Color16::new(0, 0, 0); NUM_LEDS];
+        let values: Vec<Color16> = (1..=view.len() as u16)
+            .map(|i| Color16::new(i, 0, 0))
+            .collect();
+        view.write(&values, &mut frame);
+        let mut leds: Vec<(u16, usize)> = (0..NUM_LEDS)
+            .filter(|&i| frame[i].r > 0)
+            .map(|i| (frame[i].r, i))
+            .collect();
+        leds.sort();
+        leds.into_iter().map(|(_, i)| i).collect()
+    }
+
+    #[test]
+    fn trailer_segments_cover_the_strip() {
+        let mut next = 0;
+        for segment in TRAILER.segments() {
+            assert_eq!(segment.leds.start, next);
+            next = segment.leds.end;
+        }
+        assert_eq!(next, NUM_LEDS);
+        assert_eq!(TRAILER.segment(Side::Top).unwrap().leds.len(), 34);
+    }
+
+    #[test]
+    fn corners_are_one_step_apart() {
+        let left = TRAILER.segment(Side::Left).unwrap().leds.clone();
+        let right = TRAILER.segment(Side::Right).unwrap().leds.clone();
+        assert_eq!(TRAILER.position(0), Some(Point::new(0.0, 0.0)));
+        assert_eq!(TRAILER.position(left.end - 1), Some(Point::new(0.0, 11.0)));
+        assert_eq!(TRAILER.position(left.end), Some(Point::new(1.0, 12.0)));
+        assert_eq!(TRAILER.position(right.start), Some(Point::new(35.0, 11.0)));
+        assert_eq!(
+            TRAILER.position(right.start - 1),
+            Some(Point::new(34.0, 12.0))
+        );
+        assert_eq!(TRAILER.position(NUM_LEDS - 1), Some(Point::new(35.0, 0.0)));
+        assert_eq!(TRAILER.position(NUM_LEDS), None);
+    }
+
+    #[test]
+    fn transforms() {
+        assert_eq!(lit(&View::new(2..5)), [2, 3, 4]);
+        assert_eq!(lit(&View::new(2..5).reverse()), [4, 3, 2]);
+        assert_eq!(lit(&View::new(2..5).offset(1)), [3, 4, 2]);
+        assert_eq!(lit(&View::new(2..6).mirror()), [3, 4, 2, 5]);
+        assert_eq!(lit(&View::new(2..7).mirror()), [4, 3, 5, 2, 6]);
+        assert_eq!(lit(&View::new(2..6).mirror().reverse()), [2, 5, 3, 4]);
+        assert_eq!(View::new(2..7).mirror().len(), 3);
+    }
+
+    #[test]
+    fn mirrored_strip_starts_in_the_center() {
+        let view = View::strip().mirror();
+        assert_eq!(view.len(), NUM_LEDS / 2);
+        assert_eq!(view.leds(0), (NUM_LEDS / 2, Some(NUM_LEDS / 2 - 1)));
+        assert_eq!(view.leds(view.len() - 1), (NUM_LEDS - 1, Some(0)));
+
+        let mut frame = [Color16::new(0, 0, 0); NUM_LEDS];
+        view.fill(0..2, Color16::new(1, 0, 0), &mut frame);
+        let lit: Vec<usize> = (0..NUM_LEDS).filter(|&i| frame[i].r > 0).collect();
+        assert_eq!(lit, [27, 28, 29, 30]);
+    }
+}
@@ -0,0 +1,53 @@
+//! Hardware independent part of the trailer light.
+//!
+//! Everything in here only depends on the `smart-leds` and `embedded-hal`
+//! traits, so it can be used by the ESP32-C3 firmware as well as by tools and
+//! tests running on the host.
+#![cfg_attr(not(test), no_std)]
+
+pub mod animation;
+pub mod brake;
+pub mod braking;
+pub mod button;
+pub mod color;
+pub mod combinator;
+pub mod compositor;
+pub mod controls;
+pub mod dither;
+pub mod easing;
+pub mod effect;
+pub mod effects;
+pub mod fixed;
+pub mod gamma;
+pub mod imu;
+pub mod indicator;
+pub mod kernel;
+pub mod layout;
+pub mod particles;
+pub mod power;
+pub mod sequencer;
+pub mod time;
+pub mod trailer_light;
+pub mod transition;
+pub mod vm;
+pub mod wheel;
+
+pub use animation::AnimationContext;
+pub use effect::Effect;
+pub use trailer_light::TrailerLight;
+
+use smart_leds::RGB;
+
+/// Color as sent to the LEDs.
+pub type Color = RGB<u8>;
+
+/// Color in the frame buffer. The extra bits are dithered away when the frame
+/// is written to the LEDs.
+pub type Color16 = RGB<u16>;
+
+pub const NUM_LEDS: usize = 58;
+
+/// Converts an 8 bit color to the same brightness in 16 bit.
+pub fn widen(c: Color) -> Color16 {
+    Color16::new(c.r as u16 * 257, c.g as u16 * 257, c.b as u16 * 257)
+}
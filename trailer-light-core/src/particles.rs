@@ -0,0 +1,456 @@
+//! Many highlights at once.
+//!
+//! A [`ParticleSystem`] moves up to `N` [`Particle`]s over the strip, each
+//! with its own position, velocity, width, color and decay, and max-blends
+//! them onto an ambient color like
+//! [`AnimationContext::calc_value`](crate::AnimationContext::calc_value) does
+//! for its one highlight. Particles can be spawned by hand or by a spawner at
+//! a fixed interval, and leave a fading trail if enabled. Comets, bouncing
+//! scanners and rain are all particle systems, see [`effects`](crate::effects).
+//!
+//! Unlike most effects, a particle system moves its particles by the time
+//! passed since the frame before, so `t` has to increase from call to call.
+
+use core::time::Duration;
+
+use crate::{
+    effect::{Effect, Frame},
+    kernel::Kernel,
+    widen, Color, Color16, NUM_LEDS,
+};
+
+/// Position of the last LED.
+const LAST: f32 = (NUM_LEDS - 1) as f32;
+
+/// What happens to particles reaching an end of the strip.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edge {
+    /// They leave the strip and are removed.
+    Vanish,
+    /// They come back in at the other end.
+    Wrap,
+    /// They turn around.
+    Bounce,
+}
+
+/// A highlight of a [`ParticleSystem`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Particle {
+    /// LED index of its center.
+    pub position: f32,
+    /// LEDs per second, negative values move towards the first LED.
+    pub velocity: f32,
+    /// Distance from the center at which it's dark, in LEDs.
+    pub width: f32,
+    pub color: Color,
+    /// Brightness lost per second, the particle is removed once it's dark.
+    /// 0 keeps it forever.
+    pub decay: f32,
+    brightness: f32,
+}
+
+impl Particle {
+    /// Red particle of width 2 that doesn't decay.
+    pub const fn new(position: f32, velocity: f32) -> Self {
+        Particle {
+            position,
+            velocity,
+            width: 2.0,
+            color: Color::new(255, 0, 0),
+            decay: 0.0,
+            brightness: 1.0,
+        }
+    }
+
+    pub const fn width(mut self, width: f32) -> Self {
+        self.width = width;
+        self
+    }
+
+    pub const fn color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub const fn decay(mut self, decay: f32) -> Self {
+        self.decay = decay;
+        self
+    }
+
+    /// Moves the particle by `dt` seconds, returns `false` if it's gone.
+    fn update(&mut self, dt: f32, edge: Edge) -> bool {
+        self.position += self.velocity * dt;
+        self.brightness -= self.decay * dt;
+        if self.brightness <= 0.0 {
+            return false;
+        }
+        match edge {
+            Edge::Vanish => (-self.width..=LAST + self.width).contains(&self.position),
+            Edge::Wrap => {
+                while self.position < 0.0 {
+                    self.position += NUM_LEDS as f32;
+                }
+                while self.position >= NUM_LEDS as f32 {
+                    self.position -= NUM_LEDS as f32;
+                }
+                true
+            }
+            Edge::Bounce => {
+                if self.position < 0.0 {
+                    self.position = -self.position;
+                    self.velocity = -self.velocity;
+                } else if self.position > LAST {
+                    self.position = 2.0 * LAST - self.position;
+                    self.velocity = -self.velocity;
+                }
+                // a long step may even go past the other end
+                self.position = self.position.clamp(0.0, LAST);
+                true
+            }
+        }
+    }
+
+    /// Max-blends the particle onto `frame`.
+    fn draw(&self, frame: &mut Frame, kernel: Kernel, wrap: bool) {
+        if self.width <= 0.0 {
+            return;
+        }
+        let color = widen(self.color);
+        for (i, c) in frame.iter_mut().enumerate() {
+            let mut d = i as f32 - self.position;
+            if d < 0.0 {
+                d = -d;
+            }
+            if wrap && d > NUM_LEDS as f32 / 2.0 {
+                d = NUM_LEDS as f32 - d;
+            }
+            let weight = self.brightness * kernel.apply(d / self.width);
+            if weight <= 0.0 {
+                continue;
+            }
+            let channel = |c: u16| (c as f32 * weight) as u16;
+            c.r = c.r.max(channel(color.r));
+            c.g = c.g.max(channel(color.g));
+            c.b = c.b.max(channel(color.b));
+        }
+    }
+}
+
+/// Small xorshift generator for spawners, the same seed gives the same
+/// particles.
+#[derive(Clone, Debug)]
+pub struct Rng(u32);
+
+impl Rng {
+    pub const fn new(seed: u32) -> Self {
+        // xorshift gets stuck at 0
+        Rng(if seed == 0 { 0x9e37_79b9 } else { seed })
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.0 = x;
+        x
+    }
+
+    /// Uniformly distributed in `low..high`.
+    pub fn range(&mut self, low: f32, high: f32) -> f32 {
+        let unit = (self.next_u32() >> 8) as f32 / (1 << 24) as f32;
+        low + (high - low) * unit
+    }
+}
+
+/// Makes a new particle every `interval`.
+struct Spawner {
+    interval: Duration,
+    make: fn(&mut Rng) -> Particle,
+    next: Duration,
+}
+
+/// Up to `N` particles over an ambient color.
+///
+/// Runs until all particles are gone, forever if it has a spawner.
+pub struct ParticleSystem<const N: usize> {
+    particles: [Option<Particle>; N],
+    edge: Edge,
+    kernel: Kernel,
+    ambient: Color16,
+    // time it takes the trail to fade out, `None` without a trail
+    trail: Option<Duration>,
+    // particles and their trails, without the ambient color
+    canvas: Frame,
+    spawner: Option<Spawner>,
+    rng: Rng,
+    last: Option<Duration>,
+}
+
+impl<const N: usize> ParticleSystem<N> {
+    /// Empty system on black, without trails.
+    pub fn new(edge: Edge) -> Self {
+        ParticleSystem {
+            particles: [None; N],
+            edge,
+            kernel: Kernel::Triangle,
+            ambient: Color16::new(0, 0, 0),
+            trail: None,
+            canvas: [Color16::new(0, 0, 0); NUM_LEDS],
+            spawner: None,
+            rng: Rng::new(1),
+            last: None,
+        }
+    }
+
+    /// Shape of the particles.
+    pub fn kernel(mut self, kernel: Kernel) -> Self {
+        self.kernel = kernel;
+        self
+    }
+
+    /// Color of the LEDs without particles.
+    pub fn ambient(mut self, color: Color) -> Self {
+        self.ambient = widen(color);
+        self
+    }
+
+    /// Leaves a trail behind the particles, fading out over `duration`.
+    pub fn trail(mut self, duration: Duration) -> Self {
+        self.trail = Some(duration);
+        self
+    }
+
+    /// Spawns a particle made by `make` right away and then every
+    /// `interval`, as long as there is room.
+    pub fn spawn_every(mut self, interval: Duration, make: fn(&mut Rng) -> Particle) -> Self {
+        self.spawner = Some(Spawner {
+            interval,
+            make,
+            next: Duration::ZERO,
+        });
+        self
+    }
+
+    /// Seed of the random numbers passed to the spawner.
+    pub fn seed(mut self, seed: u32) -> Self {
+        self.rng = Rng::new(seed);
+        self
+    }
+
+    /// Adds `particle`, returns `false` if all `N` are in use.
+    pub fn spawn(&mut self, particle: Particle) -> bool {
+        match self.particles.iter_mut().find(|p| p.is_none()) {
+            Some(slot) => {
+                *slot = Some(particle);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Number of particles alive.
+    pub fn len(&self) -> usize {
+        self.particles.iter().flatten().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn particles(&self) -> impl Iterator<Item = &Particle> {
+        self.particles.iter().flatten()
+    }
+
+    /// Fades the trail by `dt`, or clears the canvas without trail.
+    fn fade(&mut self, dt: Duration) {
+        let step = match self.trail {
+            Some(trail) if !trail.is_zero() => {
+                // rounded up, so the trail is gone after `trail`
+                let step = (u16::MAX as u128 * dt.as_micros()).div_ceil(trail.as_micros());
+                step.min(u16::MAX as u128) as u16
+            }
+            _ => u16::MAX,
+        };
+        for c in self.canvas.iter_mut() {
+            *c = Color16::new(
+                c.r.saturating_sub(step),
+                c.g.saturating_sub(step),
+                c.b.saturating_sub(step),
+            );
+        }
+    }
+}
+
+impl<const N: usize> Effect for ParticleSystem<N> {
+    fn render(&mut self, t: Duration, frame: &mut Frame) -> bool {
+        let dt = t.saturating_sub(self.last.unwrap_or(t));
+        self.last = Some(t);
+
+        for slot in self.particles.iter_mut() {
+            if let Some(p) = slot {
+                if !p.update(dt.as_secs_f32(), self.edge) {
+                    *slot = None;
+                }
+            }
+        }
+        if let Some(spawner) = &mut self.spawner {
+            if spawner.next <= t {
+                let particle = (spawner.make)(&mut self.rng);
+                if let Some(slot) = self.particles.iter_mut().find(|p| p.is_none()) {
+                    *slot = Some(particle);
+                }
+                // one particle after a jump in time, e.g. a pause, not one for
+                // every interval missed, but still in the same rhythm
+                let interval = spawner.interval.max(Duration::from_micros(1));
+                let late = (t - spawner.next).as_micros() % interval.as_micros();
+                spawner.next = t + interval - Duration::from_micros(late as u64);
+            }
+        }
+
+        self.fade(dt);
+        for p in self.particles.iter().flatten() {
+            p.draw(&mut self.canvas, self.kernel, self.edge == Edge::Wrap);
+        }
+        for (c, p) in frame.iter_mut().zip(&self.canvas) {
+            *c = Color16::new(
+                self.ambient.r.max(p.r),
+                self.ambient.g.max(p.g),
+                self.ambient.b.max(p.b),
+            );
+        }
+        self.spawner.is_some() || !self.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    fn render<const N: usize>(system: &mut ParticleSystem<N>, t: Duration) -> (bool, Frame) {
+        let mut frame = [Color16::new(1, 1, 1); NUM_LEDS];
+        let running = system.render(t, &mut frame);
+        (running, frame)
+    }
+
+    fn position<const N: usize>(system: &ParticleSystem<N>) -> f32 {
+        system.particles().next().unwrap().position
+    }
+
+    #[test]
+    fn particles_move_with_their_velocity() {
+        let mut system = ParticleSystem::<1>::new(Edge::Vanish);
+        system.spawn(Particle::new(10.0, 20.0));
+        render(&mut system, ms(0));
+        render(&mut system, ms(250));
+        assert_eq!(position(&system), 15.0);
+
+        let (running, frame) = render(&mut system, ms(500));
+        assert!(running);
+        assert_eq!(frame[20], widen(Color::new(255, 0, 0)));
+        assert_eq!(frame[21].r, u16::MAX / 2);
+        assert_eq!(frame[22], Color16::new(0, 0, 0));
+    }
+
+    #[test]
+    fn edges() {
+        let mut bounce = ParticleSystem::<1>::new(Edge::Bounce);
+        bounce.spawn(Particle::new(LAST - 1.0, 10.0));
+        render(&mut bounce, ms(0));
+        render(&mut bounce, ms(300));
+        assert_eq!(position(&bounce), LAST - 2.0);
+        assert!(bounce.particles().next().unwrap().velocity < 0.0);
+
+        let mut wrap = ParticleSystem::<1>::new(Edge::Wrap);
+        wrap.spawn(Particle::new(1.0, -10.0));
+        render(&mut wrap, ms(0));
+        let (_, frame) = render(&mut wrap, ms(200));
+        assert_eq!(position(&wrap), LAST);
+        // the particle shows on both ends
+        assert!(frame[0].r > 0 && frame[NUM_LEDS - 1].r > 0);
+
+        let mut vanish = ParticleSystem::<1>::new(Edge::Vanish);
+        vanish.spawn(Particle::new(1.0, -10.0));
+        render(&mut vanish, ms(0));
+        assert!(!render(&mut vanish, ms(400)).0);
+        assert!(vanish.is_empty());
+    }
+
+    #[test]
+    fn decayed_particles_are_removed() {
+        let mut system = ParticleSystem::<1>::new(Edge::Vanish);
+        system.spawn(Particle::new(10.0, 0.0).decay(2.0));
+        render(&mut system, ms(0));
+        let (_, frame) = render(&mut system, ms(250));
+        assert_eq!(frame[10].r, u16::MAX / 2);
+        assert!(!render(&mut system, ms(500)).0);
+    }
+
+    #[test]
+    fn blends_onto_the_ambient_color() {
+        let mut system = ParticleSystem::<2>::new(Edge::Vanish).ambient(Color::new(0, 0, 100));
+        system.spawn(Particle::new(10.0, 0.0));
+        system.spawn(Particle::new(11.0, 0.0).color(Color::new(0, 255, 0)));
+        assert!(!system.spawn(Particle::new(12.0, 0.0)));
+
+        let (_, frame) = render(&mut system, ms(0));
+        assert_eq!(frame[0], Color16::new(0, 0, 100 * 257));
+        assert_eq!(frame[10], Color16::new(u16::MAX, u16::MAX / 2, 100 * 257));
+    }
+
+    #[test]
+    fn trail_fades_out() {
+        let mut system = ParticleSystem::<1>::new(Edge::Vanish).trail(ms(100));
+        system.spawn(Particle::new(10.0, 100.0).width(0.5));
+        render(&mut system, ms(0));
+        let (_, frame) = render(&mut system, ms(50));
+        assert_eq!(position(&system), 15.0);
+        assert!(frame[10].r.abs_diff(u16::MAX / 2) <= 1);
+        let (_, frame) = render(&mut system, ms(100));
+        assert_eq!(frame[10].r, 0);
+    }
+
+    #[test]
+    fn spawner_fills_up_to_capacity() {
+        let mut system = ParticleSystem::<3>::new(Edge::Vanish)
+            .spawn_every(ms(10), |rng| Particle::new(rng.range(0.0, LAST), 0.0))
+            .seed(7);
+        assert!(render(&mut system, ms(0)).0);
+        assert_eq!(system.len(), 1);
+        render(&mut system, ms(10));
+        render(&mut system, ms(25));
+        assert_eq!(system.len(), 3);
+        render(&mut system, ms(100));
+        assert_eq!(system.len(), 3);
+        assert!(system
+            .particles()
+            .all(|p| (0.0..LAST).contains(&p.position)));
+    }
+
+    #[test]
+    fn spawner_skips_missed_intervals() {
+        let mut system = ParticleSystem::<8>::new(Edge::Vanish)
+            .spawn_every(ms(10), |_| Particle::new(10.0, 0.0));
+        render(&mut system, ms(0));
+        render(&mut system, ms(55));
+        assert_eq!(system.len(), 2);
+        // back in the rhythm
+        render(&mut system, ms(59));
+        assert_eq!(system.len(), 2);
+        render(&mut system, ms(60));
+        assert_eq!(system.len(), 3);
+    }
+
+    #[test]
+    fn long_steps_bounce_onto_the_strip() {
+        let mut bounce = ParticleSystem::<1>::new(Edge::Bounce);
+        bounce.spawn(Particle::new(10.0, 1000.0));
+        render(&mut bounce, ms(0));
+        render(&mut bounce, ms(1000));
+        assert!((0.0..=LAST).contains(&position(&bounce)));
+    }
+}
@@ -0,0 +1,296 @@
+use crate::{Color, Color16};
+
+// powerbank max output is 5V * 2.1A = 10.5W
+// Power consumption per LED: 0.3W for full white
+
+pub const MAX_MILLIAMPS: usize = 2100; // that's the maximum the powerbank can provide
+pub const MAX_MILLIWATTS: usize = 5 * MAX_MILLIAMPS;
+pub const MICROCONTROLLER_CONSUMPTION_MW: usize = 1500; // just guessing...
+
+/// Power left for the LEDs.
+pub const LED_BUDGET_MW: usize = MAX_MILLIWATTS - MICROCONTROLLER_CONSUMPTION_MW;
+
+/// Scale factor of a frame that isn't throttled, see [`ThrottleStats`].
+pub const FULL_SCALE: u16 = 256;
+
+/// Predicts the power drawn by the LEDs.
+///
+/// Every channel draws current proportional to its value, on top of the
+/// quiescent current every LED draws even when it's dark.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PowerModel {
+    /// Current of the red channel at full scale, in µA.
+    pub red_ua: u32,
+    /// Current of the green channel at full scale, in µA.
+    pub green_ua: u32,
+    /// Current of the blue channel at full scale, in µA.
+    pub blue_ua: u32,
+    /// Quiescent current per LED, in µA.
+    pub idle_ua: u32,
+    pub supply_mv: u32,
+}
+
+impl PowerModel {
+    /// WS2812B running off the powerbank: 20 mA per channel, 1 mA idle.
+    pub const WS2812B: PowerModel = PowerModel {
+        red_ua: 20_000,
+        green_ua: 20_000,
+        blue_ua: 20_000,
+        idle_ua: 1_000,
+        supply_mv: 5_000,
+    };
+
+    /// Predicted power of the LEDs showing `frame`, rounded up.
+    pub fn frame_milliwatts(&self, frame: &[Color]) -> usize {
+        let channels = frame.iter().map(|c| [c.r as u64, c.g as u64, c.b as u64]);
+        self.milliwatts(channels, u8::MAX as u64)
+    }
+
+    /// Like [`frame_milliwatts`](Self::frame_milliwatts), for a 16 bit frame.
+    pub fn frame16_milliwatts(&self, frame: &[Color16]) -> usize {
+        let channels = frame.iter().map(|c| [c.r as u64, c.g as u64, c.b as u64]);
+        self.milliwatts(channels, u16::MAX as u64)
+    }
+
+    fn milliwatts(&self, leds: impl Iterator<Item = [u64; 3]>, full_scale: u64) -> usize {
+        // sum up in units of 1/full_scale µA to round only once
+        let mut sum = 0;
+        for [r, g, b] in leds {
+            sum += r * self.red_ua as u64
+                + g * self.green_ua as u64
+                + b * self.blue_ua as u64
+                + self.idle_ua as u64 * full_scale;
+        }
+        (sum * self.supply_mv as u64).div_ceil(full_scale * 1_000_000) as usize
+    }
+
+    /// Power of `num_leds` dark LEDs.
+    pub fn idle_milliwatts(&self, num_leds: usize) -> usize {
+        (num_leds as u64 * self.idle_ua as u64 * self.supply_mv as u64).div_ceil(1_000_000) as usize
+    }
+
+    /// Whether `frame` can be shown without overloading the powerbank.
+    pub fn within_budget(&self, frame: &[Color]) -> bool {
+        self.frame_milliwatts(frame) <= LED_BUDGET_MW
+    }
+}
+
+impl Default for PowerModel {
+    fn default() -> Self {
+        Self::WS2812B
+    }
+}
+
+/// How often and how hard the [`PowerLimiter`] had to dim frames.
+///
+/// Scale factors are in units of 1/256, [`FULL_SCALE`] means the frame was
+/// shown as is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ThrottleStats {
+    pub frames: u32,
+    pub throttled_frames: u32,
+    pub last_scale: u16,
+    pub min_scale: u16,
+}
+
+impl Default for ThrottleStats {
+    fn default() -> Self {
+        ThrottleStats {
+            frames: 0,
+            throttled_frames: 0,
+            last_scale: FULL_SCALE,
+            min_scale: FULL_SCALE,
+        }
+    }
+}
+
+/// Dims frames that would exceed the power budget.
+///
+/// All channels of the frame are scaled by the same factor, so the relative
+/// brightness stays the same. Channels that are on are never scaled down to
+/// zero, the light doesn't go dark no matter how far it's over budget.
+pub struct PowerLimiter {
+    budget_mw: usize,
+    model: PowerModel,
+    stats: ThrottleStats,
+}
+
+impl PowerLimiter {
+    pub fn new(budget_mw: usize, model: PowerModel) -> Self {
+        PowerLimiter {
+            budget_mw,
+            model,
+            stats: ThrottleStats::default(),
+        }
+    }
+
+    pub fn model(&self) -> &PowerModel {
+        &self.model
+    }
+
+    pub fn set_model(&mut self, model: PowerModel) {
+        self.model = model;
+    }
+
+    pub fn stats(&self) -> ThrottleStats {
+        self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = ThrottleStats::default();
+    }
+
+    /// Writes `frame` into `out`, dimmed as far as needed to fit the budget.
+    /// Returns the applied scale factor.
+    pub fn limit(&mut self, frame: &[Color16], out: &mut [Color16]) -> u16 {
+        out.copy_from_slice(frame);
+        let mut scale = FULL_SCALE;
+        if self.model.frame16_milliwatts(frame) > self.budget_mw {
+            // Only the channels can be dimmed, the idle power stays. Works
+            // in the exact units of the model, so rounding the scale down
+            // keeps the frame within budget.
+            let full_scale = u16::MAX as u64;
+            let idle = frame.len() as u64 * self.model.idle_ua as u64 * full_scale;
+            let channels = frame
+                .iter()
+                .map(|c| {
+                    c.r as u64 * self.model.red_ua as u64
+                        + c.g as u64 * self.model.green_ua as u64
+                        + c.b as u64 * self.model.blue_ua as u64
+                })
+                .sum::<u64>()
+                .max(1);
+            let budget =
+                self.budget_mw as u64 * full_scale * 1_000_000 / self.model.supply_mv.max(1) as u64;
+            scale = (budget.saturating_sub(idle) * FULL_SCALE as u64 / channels) as u16;
+            scale_frame(frame, out, scale);
+        }
+
+        self.stats.frames = self.stats.frames.wrapping_add(1);
+        if scale < FULL_SCALE {
+            self.stats.throttled_frames = self.stats.throttled_frames.wrapping_add(1);
+        }
+        self.stats.last_scale = scale;
+        self.stats.min_scale = self.stats.min_scale.min(scale);
+        scale
+    }
+}
+
+fn scale_frame(frame: &[Color16], out: &mut [Color16], scale: u16) {
+    // Don't dim channels that are on below one 8 bit step, so they aren't
+    // dithered away.
+    let scale_channel = |c: u16| {
+        let scaled = ((c as u32 * scale as u32) >> 8) as u16;
+        scaled.max(c.min(257))
+    };
+    for (o, c) in out.iter_mut().zip(frame) {
+        *o = Color16::new(scale_channel(c.r), scale_channel(c.g), scale_channel(c.b));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{widen, NUM_LEDS};
+
+    #[test]
+    fn full_white_is_over_budget() {
+        let model = PowerModel::default();
+        assert!(!model.within_budget(&[Color::new(255, 255, 255); NUM_LEDS]));
+    }
+
+    #[test]
+    fn full_red_is_within_budget() {
+        let model = PowerModel::default();
+        let frame = [Color::new(255, 0, 0); NUM_LEDS];
+        assert_eq!(model.frame_milliwatts(&frame), 58 * 21 * 5);
+        assert!(model.within_budget(&frame));
+    }
+
+    #[test]
+    fn channels_are_weighted() {
+        let model = PowerModel {
+            red_ua: 10_000,
+            green_ua: 5_000,
+            blue_ua: 1_000,
+            idle_ua: 0,
+            supply_mv: 1_000,
+        };
+        assert_eq!(model.frame_milliwatts(&[Color::new(255, 0, 0)]), 10);
+        assert_eq!(model.frame_milliwatts(&[Color::new(0, 255, 0)]), 5);
+        assert_eq!(model.frame_milliwatts(&[Color::new(0, 0, 255)]), 1);
+        // rounded up, not truncated
+        assert_eq!(model.frame_milliwatts(&[Color::new(1, 0, 0)]), 1);
+    }
+
+    #[test]
+    fn dark_leds_draw_idle_power() {
+        let model = PowerModel::default();
+        let frame = [Color::new(0, 0, 0); NUM_LEDS];
+        assert_eq!(model.frame_milliwatts(&frame), 290);
+        assert_eq!(model.idle_milliwatts(NUM_LEDS), 290);
+    }
+
+    #[test]
+    fn full_scale_matches_8_bit() {
+        let model = PowerModel::default();
+        let frame = [Color::new(255, 30, 2); NUM_LEDS];
+        assert_eq!(
+            model.frame16_milliwatts(&frame.map(widen)),
+            model.frame_milliwatts(&frame)
+        );
+    }
+
+    #[test]
+    fn frames_within_budget_are_unchanged() {
+        let mut limiter = PowerLimiter::new(LED_BUDGET_MW, PowerModel::default());
+        let frame = [widen(Color::new(255, 0, 0)); NUM_LEDS];
+        let mut out = [Color16::default(); NUM_LEDS];
+        assert_eq!(limiter.limit(&frame, &mut out), FULL_SCALE);
+        assert_eq!(out, frame);
+        assert_eq!(limiter.stats().throttled_frames, 0);
+    }
+
+    #[test]
+    fn over_budget_frames_are_scaled_to_fit() {
+        let mut limiter = PowerLimiter::new(LED_BUDGET_MW, PowerModel::default());
+        let mut frame = [widen(Color::new(255, 255, 255)); NUM_LEDS];
+        frame[0] = Color16::new(32768, 16384, 0);
+        let mut out = [Color16::default(); NUM_LEDS];
+
+        let scale = limiter.limit(&frame, &mut out);
+        assert!(scale < FULL_SCALE);
+        assert!(limiter.model().frame16_milliwatts(&out) <= LED_BUDGET_MW);
+        // relative brightness is kept
+        assert_eq!(out[0].g, out[0].r / 2);
+        assert_eq!(out[0].b, 0);
+        assert!(out[0].r < out[1].r);
+
+        limiter.limit(&[Color16::new(1, 0, 0); NUM_LEDS], &mut out);
+        let stats = limiter.stats();
+        assert_eq!(stats.frames, 2);
+        assert_eq!(stats.throttled_frames, 1);
+        assert_eq!(stats.min_scale, scale);
+        assert_eq!(stats.last_scale, FULL_SCALE);
+    }
+
+    #[test]
+    fn scale_is_as_bright_as_the_budget_allows() {
+        let mut limiter = PowerLimiter::new(LED_BUDGET_MW, PowerModel::default());
+        let frame = [Color16::new(60_000, 50_000, 40_000); NUM_LEDS];
+        let mut out = [Color16::default(); NUM_LEDS];
+        let scale = limiter.limit(&frame, &mut out);
+        assert!(limiter.model().frame16_milliwatts(&out) <= LED_BUDGET_MW);
+        scale_frame(&frame, &mut out, scale + 1);
+        assert!(limiter.model().frame16_milliwatts(&out) > LED_BUDGET_MW);
+    }
+
+    #[test]
+    fn never_blanks_the_light() {
+        let mut limiter = PowerLimiter::new(0, PowerModel::default());
+        let frame = [Color16::new(65535, 0, 100); NUM_LEDS];
+        let mut out = [Color16::default(); NUM_LEDS];
+        limiter.limit(&frame, &mut out);
+        assert!(out.iter().all(|c| c.r >= 257 && c.g == 0 && c.b == 100));
+    }
+}
@@ -0,0 +1,364 @@
+//! Playing a list of scenes.
+//!
+//! A playlist is a slice of [`Step`]s, each playing a [`Scene`] for some time,
+//! a number of times or until an event, then continuing with the next step or
+//! jumping to another one. Events can also branch off a step early. This
+//! keeps the behaviour of the light as data, see [`SHOW`].
+//!
+//! `E` is the type of the events, e.g. an enum of the inputs of the light.
+
+use core::time::Duration;
+
+use crate::{
+    effect::{Frame, Player, Runner},
+    effects::{Scene, SceneEffect},
+    time::Instant,
+    transition::{self, Transition},
+    Color,
+};
+
+/// When a step is done.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Until<E> {
+    /// After the given time, the effect is played again should it finish
+    /// before.
+    Elapsed(Duration),
+    /// Once the effect has finished the given number of times.
+    Played(u16),
+    /// Once the event happens, the effect is played again whenever it
+    /// finishes.
+    Event(E),
+    /// Never, the effect is played again whenever it finishes.
+    Forever,
+}
+
+/// Where to continue once a step is done.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Next {
+    /// With the following step, the playlist ends after the last one.
+    Continue,
+    /// With the step at the given index.
+    Goto(usize),
+    /// Nowhere, the playlist ends and the last frame is kept.
+    Stop,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Step<'a, E = ()> {
+    pub scene: Scene,
+    pub until: Until<E>,
+    pub next: Next,
+    /// Fades over from the step before, cuts if `None`.
+    pub transition: Option<Transition>,
+    /// Events jumping to another step while this one is played.
+    pub branches: &'a [(E, usize)],
+}
+
+impl<'a, E> Step<'a, E> {
+    pub const fn new(scene: Scene, until: Until<E>) -> Self {
+        Step {
+            scene,
+            until,
+            next: Next::Continue,
+            transition: None,
+            branches: &[],
+        }
+    }
+
+    pub const fn then(mut self, next: Next) -> Self {
+        self.next = next;
+        self
+    }
+
+    pub const fn fade_in(mut self, transition: Transition) -> Self {
+        self.transition = Some(transition);
+        self
+    }
+
+    pub const fn branches(mut self, branches: &'a [(E, usize)]) -> Self {
+        self.branches = branches;
+        self
+    }
+}
+
+/// Start-up of the light: a short pause, blinking, and the turn-on animation
+/// held for five seconds. Playlists continue with what the light plays while
+/// riding, see [`concat()`].
+pub const fn boot<E: Copy>() -> [Step<'static, E>; 4] {
+    [
+        Step::new(
+            Scene::Solid(Color::new(0, 0, 0)),
+            Until::Elapsed(Duration::from_millis(500)),
+        ),
+        Step::new(Scene::Blink, Until::Played(1)),
+        Step::new(Scene::TurnOn, Until::Played(1)),
+        Step::new(Scene::Hold, Until::Elapsed(Duration::from_millis(5000))),
+    ]
+}
+
+/// The steps of `first` followed by those of `then`, to build playlists out
+/// of others at compile time. `N` is the length of both together.
+///
+/// # Panics
+///
+/// If `N` isn't `A + B`, or is 0.
+pub const fn concat<'a, E: Copy, const A: usize, const B: usize, const N: usize>(
+    first: [Step<'a, E>; A],
+    then: [Step<'a, E>; B],
+) -> [Step<'a, E>; N] {
+    assert!(A + B == N && N > 0);
+    let mut steps = [if A > 0 { first[0] } else { then[0] }; N];
+    let mut i = 0;
+    while i < A {
+        steps[i] = first[i];
+        i += 1;
+    }
+    while i < N {
+        steps[i] = then[i - A];
+        i += 1;
+    }
+    steps
+}
+
+/// Start-up and riding behaviour of the light: the [`boot`] sequence, then
+/// the wave forever.
+pub static SHOW: [Step; 5] = concat(
+    boot(),
+    [Step::new(Scene::Wave, Until::Forever).fade_in(transition::MODE_CHANGE)],
+);
+
+/// Plays a playlist of [`Step`]s.
+pub struct Sequencer<'a, E = ()> {
+    steps: &'a [Step<'a, E>],
+    current: Option<usize>,
+    step_start: Instant,
+    plays: u16,
+    runner: Runner<SceneEffect>,
+}
+
+impl<'a, E: Copy + PartialEq> Sequencer<'a, E> {
+    pub fn new(steps: &'a [Step<'a, E>]) -> Self {
+        Sequencer {
+            steps,
+            current: None,
+            step_start: Instant::default(),
+            plays: 0,
+            runner: Runner::new(),
+        }
+    }
+
+    /// Starts playing from the first step.
+    pub fn start(&mut self, now: Instant) {
+        self.enter(0, now);
+    }
+
+    /// Index of the step being played, `None` before the start and after the
+    /// end.
+    pub fn current(&self) -> Option<usize> {
+        self.current
+    }
+
+    /// How often the effect of the current step has finished so far.
+    pub fn plays(&self) -> u16 {
+        self.plays
+    }
+
+    /// Ends the current step if it waits for `event`, otherwise follows the
+    /// step's branch for it, if any.
+    pub fn event(&mut self, event: E, now: Instant) {
+        let Some(step) = self.current.map(|i| &self.steps[i]) else {
+            return;
+        };
+        if step.until == Until::Event(event) {
+            self.advance(now);
+        } else if let Some(&(_, to)) = step.branches.iter().find(|(e, _)| *e == event) {
+            self.enter(to, now);
+        }
+    }
+
+    fn enter(&mut self, i: usize, now: Instant) {
+        let Some(step) = self.steps.get(i) else {
+            self.current = None;
+            return;
+        };
+        self.current = Some(i);
+        self.step_start = now;
+        self.plays = 0;
+        match step.transition {
+            Some(transition) => self.runner.crossfade(step.scene.effect(), now, transition),
+            None => self.runner.start(step.scene.effect(), now),
+        }
+    }
+
+    fn advance(&mut self, now: Instant) {
+        let Some(i) = self.current else {
+            return;
+        };
+        match self.steps[i].next {
+            Next::Continue => self.enter(i + 1, now),
+            Next::Goto(to) => self.enter(to, now),
+            Next::Stop => self.current = None,
+        }
+    }
+
+    /// Advances to the next step, returns whether there is one.
+    fn end_step(&mut self, now: Instant) -> bool {
+        self.advance(now);
+        self.current.is_some()
+    }
+}
+
+impl<E: Copy + PartialEq> Player for Sequencer<'_, E> {
+    /// Renders the current step at `now`. Returns `false` once the playlist
+    /// has ended, the frame then stays at the last one.
+    fn render(&mut self, now: Instant, frame: &mut Frame) -> bool {
+        let Some(i) = self.current else {
+            return false;
+        };
+        let step = &self.steps[i];
+        self.runner.render(now, frame);
+
+        if !self.runner.has_effect() {
+            self.plays = self.plays.saturating_add(1);
+            match step.until {
+                Until::Played(n) if self.plays >= n => return self.end_step(now),
+                _ => self.runner.restart(step.scene.effect(), now),
+            }
+        }
+        match step.until {
+            Until::Elapsed(d) if now - self.step_start >= d => self.end_step(now),
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{effects::VAL_3, Color16, NUM_LEDS};
+
+    fn at_ms(ms: u64) -> Instant {
+        Instant::from_micros(ms * 1_000)
+    }
+
+    const RED: Scene = Scene::Solid(Color::new(255, 0, 0));
+    const GREEN: Scene = Scene::Solid(Color::new(0, 255, 0));
+
+    /// Plays `sequencer` in 1 ms steps from `from` to `to`, returns the
+    /// current step of every frame.
+    fn play<E: Copy + PartialEq>(
+        sequencer: &mut Sequencer<E>,
+        from: u64,
+        to: u64,
+    ) -> Vec<Option<usize>> {
+        let mut frame = [Color16::default(); NUM_LEDS];
+        (from..to)
+            .map(|ms| {
+                sequencer.render(at_ms(ms), &mut frame);
+                sequencer.current()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn steps_follow_each_other() {
+        let steps = [
+            Step::<()>::new(RED, Until::Elapsed(Duration::from_millis(10))),
+            Step::new(Scene::EmergencyBrake, Until::Played(2)),
+        ];
+        let mut sequencer = Sequencer::new(&steps);
+        sequencer.start(at_ms(0));
+
+        let current = play(&mut sequencer, 0, 3000);
+        // the emergency brake takes 1 s, it starts after the frame at 10 ms
+        assert_eq!(current.iter().filter(|c| **c == Some(0)).count(), 10);
+        assert_eq!(current.iter().filter(|c| **c == Some(1)).count(), 2000);
+        assert_eq!(current[2010], None);
+
+        // the last frame is kept
+        let mut frame = [Color16::default(); NUM_LEDS];
+        assert!(!sequencer.render(at_ms(3000), &mut frame));
+    }
+
+    #[test]
+    fn goto_loops() {
+        let steps = [
+            Step::<()>::new(RED, Until::Elapsed(Duration::from_millis(10))),
+            Step::new(GREEN, Until::Elapsed(Duration::from_millis(10))).then(Next::Goto(0)),
+        ];
+        let mut sequencer = Sequencer::new(&steps);
+        sequencer.start(at_ms(0));
+
+        let current = play(&mut sequencer, 0, 1000);
+        assert!(current.iter().all(Option::is_some));
+        assert_eq!(current[995], Some(1));
+        assert_eq!(current[985], Some(0));
+    }
+
+    #[test]
+    fn programs_are_played_like_scenes() {
+        use crate::vm::{COLOR, END, FILL, WAIT};
+
+        static BLINK: [u8; 18] = [
+            COLOR, 0, 0, 255, FILL, 0, 1, WAIT, 10, 0, COLOR, 0, 0, 0, FILL, 0, 1, END,
+        ];
+        let steps = [
+            Step::<()>::new(Scene::Program(&BLINK), Until::Played(2)),
+            Step::new(GREEN, Until::Forever),
+        ];
+        let mut sequencer = Sequencer::new(&steps);
+        sequencer.start(at_ms(0));
+        let mut frame = [Color16::default(); NUM_LEDS];
+        sequencer.render(at_ms(5), &mut frame);
+        assert!(frame[0].b > 0);
+        sequencer.render(at_ms(15), &mut frame);
+        assert_eq!(frame[0].b, 0);
+        assert_eq!(play(&mut sequencer, 15, 30)[10], Some(1));
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    enum Event {
+        Brake,
+        Released,
+    }
+
+    #[test]
+    fn events_end_steps_and_branch() {
+        let steps = [
+            Step::new(GREEN, Until::Forever).branches(&[(Event::Brake, 1)]),
+            Step::new(RED, Until::Event(Event::Released)).then(Next::Goto(0)),
+        ];
+        let mut sequencer = Sequencer::new(&steps);
+        sequencer.start(at_ms(0));
+        play(&mut sequencer, 0, 100);
+        assert_eq!(sequencer.current(), Some(0));
+
+        // not handled by the current step
+        sequencer.event(Event::Released, at_ms(100));
+        assert_eq!(sequencer.current(), Some(0));
+
+        sequencer.event(Event::Brake, at_ms(100));
+        assert_eq!(sequencer.current(), Some(1));
+        play(&mut sequencer, 100, 200);
+        assert_eq!(sequencer.current(), Some(1));
+
+        sequencer.event(Event::Released, at_ms(200));
+        assert_eq!(sequencer.current(), Some(0));
+    }
+
+    #[test]
+    fn show_ends_in_the_wave() {
+        let mut sequencer = Sequencer::new(&SHOW);
+        sequencer.start(at_ms(0));
+        let mut frame = [Color16::default(); NUM_LEDS];
+        for ms in 0..30_000 {
+            assert!(sequencer.render(at_ms(ms), &mut frame));
+            // once turned on, the light never goes below the wave's base
+            if sequencer.current() >= Some(3) {
+                assert!(frame.iter().all(|c| c.r >= (VAL_3 * 257.0) as u16 - 1));
+            }
+        }
+        assert_eq!(sequencer.current(), Some(4));
+        assert!(sequencer.plays() > 1);
+    }
+}
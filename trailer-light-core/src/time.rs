@@ -0,0 +1,70 @@
+//! Monotonic time for driving the animations.
+//!
+//! Animations are a function of the time since they started rather than of
+//! the number of frames written, so they look the same no matter how long
+//! writing a frame takes.
+
+use core::{
+    ops::{Add, Sub},
+    time::Duration,
+};
+
+/// Point in time, in microseconds since an arbitrary start (usually boot).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Instant(u64);
+
+impl Instant {
+    pub const fn from_micros(us: u64) -> Self {
+        Instant(us)
+    }
+
+    pub const fn as_micros(&self) -> u64 {
+        self.0
+    }
+
+    /// Time elapsed since `earlier`, zero if `earlier` is later than `self`.
+    pub fn duration_since(&self, earlier: Instant) -> Duration {
+        Duration::from_micros(self.0.saturating_sub(earlier.0))
+    }
+}
+
+impl Add<Duration> for Instant {
+    type Output = Instant;
+
+    fn add(self, rhs: Duration) -> Instant {
+        Instant(self.0 + rhs.as_micros() as u64)
+    }
+}
+
+impl Sub for Instant {
+    type Output = Duration;
+
+    fn sub(self, rhs: Instant) -> Duration {
+        self.duration_since(rhs)
+    }
+}
+
+/// Source of monotonic time, e.g. the ESP32-C3 systimer.
+pub trait Clock {
+    fn now(&self) -> Instant;
+}
+
+impl<C: Clock> Clock for &C {
+    fn now(&self) -> Instant {
+        (*self).now()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arithmetic() {
+        let a = Instant::from_micros(1_000);
+        let b = a + Duration::from_millis(2);
+        assert_eq!(b.as_micros(), 3_000);
+        assert_eq!(b - a, Duration::from_millis(2));
+        assert_eq!(a - b, Duration::ZERO);
+    }
+}
@@ -0,0 +1,336 @@
+use core::fmt::Debug;
+
+use embedded_hal::blocking::delay::{DelayMs, DelayUs};
+use smart_leds::{SmartLedsWrite, RGB};
+
+use crate::{
+    dither::TemporalDither,
+    effect::{Effect, Player, Runner},
+    gamma::{self, Gamma},
+    power::{PowerLimiter, PowerModel, ThrottleStats, LED_BUDGET_MW},
+    time::{Clock, Instant},
+    widen, Color16, NUM_LEDS,
+};
+
+pub struct TrailerLight<L, D, C>
+where
+    L: SmartLedsWrite<Color = RGB<u8>>,
+    L::Error: Debug,
+    D: DelayMs<u16> + DelayUs<u16>,
+    C: Clock,
+{
+    led: L,
+    data: [Color16; NUM_LEDS],
+    delay: D,
+    clock: C,
+    gamma: &'static Gamma,
+    limiter: PowerLimiter,
+    dither: TemporalDither<NUM_LEDS>,
+}
+
+impl<L, D, C> TrailerLight<L, D, C>
+where
+    L: SmartLedsWrite<Color = RGB<u8>>,
+    L::Error: Debug,
+    D: DelayMs<u16> + DelayUs<u16>,
+    C: Clock,
+{
+    pub fn new(led: L, delay: D, clock: C) -> Self {
+        TrailerLight {
+            led,
+            data: [Color16::new(0, 0, 0); NUM_LEDS],
+            delay,
+            clock,
+            gamma: &gamma::LINEAR,
+            limiter: PowerLimiter::new(LED_BUDGET_MW, PowerModel::default()),
+            dither: TemporalDither::new(),
+        }
+    }
+
+    /// The LED driver the frames are written to.
+    pub fn led(&self) -> &L {
+        &self.led
+    }
+
+    pub fn into_led(self) -> L {
+        self.led
+    }
+
+    /// How often frames had to be dimmed to stay within the power budget.
+    pub fn throttle_stats(&self) -> ThrottleStats {
+        self.limiter.stats()
+    }
+
+    /// Sets the curve mapping the brightness of the animations to the
+    /// values sent to the LEDs. Defaults to [`gamma::LINEAR`].
+    pub fn set_gamma(&mut self, gamma: &'static Gamma) {
+        self.gamma = gamma;
+    }
+
+    /// Replaces the model used to check frames against the power budget.
+    pub fn set_power_model(&mut self, model: PowerModel) {
+        self.limiter.set_model(model);
+    }
+
+    pub fn color(&mut self, color: RGB<u8>) {
+        self.data = [widen(color); NUM_LEDS];
+        self.write_leds();
+    }
+
+    pub fn now(&self) -> Instant {
+        self.clock.now()
+    }
+
+    pub fn delay_ms(&mut self, ms: u16) {
+        self.delay.delay_ms(ms);
+    }
+
+    pub fn black(&mut self) {
+        self.color(RGB::new(0, 0, 0));
+    }
+
+    /// Plays `effect` until it has finished.
+    pub fn run(&mut self, effect: &mut dyn Effect) {
+        let mut runner = Runner::new();
+        runner.start(effect, self.clock.now());
+        self.play(&mut runner);
+    }
+
+    /// Writes the frames of `player` until it has nothing left to play,
+    /// e.g. until the effect and any transition of a [`Runner`] have finished.
+    pub fn play(&mut self, player: &mut impl Player) {
+        while self.step(player) {}
+    }
+
+    /// Renders the frame of `player` at the current time and writes it to
+    /// the LEDs. Returns `false` once it has nothing left to play.
+    pub fn step(&mut self, player: &mut impl Player) -> bool {
+        let running = player.render(self.clock.now(), &mut self.data);
+        self.write_leds();
+        running
+    }
+
+    fn write_leds(&mut self) {
+        let mut corrected = self.data;
+        for c in corrected.iter_mut() {
+            *c = self.gamma.correct(*c);
+        }
+
+        // dim the frame if it would exceed the power budget
+        let mut limited = [Color16::new(0, 0, 0); NUM_LEDS];
+        self.limiter.limit(&corrected, &mut limited);
+
+        let mut frame = [RGB::new(0, 0, 0); NUM_LEDS];
+        self.dither.dither(&limited, &mut frame);
+
+        self.led.write(frame.iter().cloned()).unwrap();
+        self.delay.delay_us(500u16);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::{cell::Cell, convert::Infallible, time::Duration};
+
+    use super::*;
+    use crate::{
+        brake::BrakeLight,
+        compositor::{BlendMode, Compositor, Layer},
+        easing::Easing,
+        effects::{Blink, EmergencyBrake, TurnOn, Wave, VAL_3},
+        power::FULL_SCALE,
+        transition::Transition,
+        Color,
+    };
+
+    #[derive(Default)]
+    struct MockLeds {
+        frames: Vec<Vec<Color>>,
+    }
+
+    impl SmartLedsWrite for MockLeds {
+        type Error = Infallible;
+        type Color = Color;
+
+        fn write<T, I>(&mut self, iterator: T) -> Result<(), Self::Error>
+        where
+            T: Iterator<Item = I>,
+            I: Into<Self::Color>,
+        {
+            self.frames.push(iterator.map(Into::into).collect());
+            Ok(())
+        }
+    }
+
+    struct NoDelay;
+
+    impl DelayMs<u16> for NoDelay {
+        fn delay_ms(&mut self, _ms: u16) {}
+    }
+
+    impl DelayUs<u16> for NoDelay {
+        fn delay_us(&mut self, _us: u16) {}
+    }
+
+    /// Advances by `step` microseconds every time it's read.
+    struct StepClock {
+        now: Cell<u64>,
+        step: u64,
+    }
+
+    impl Clock for StepClock {
+        fn now(&self) -> Instant {
+            let now = self.now.get();
+            self.now.set(now + self.step);
+            Instant::from_micros(now)
+        }
+    }
+
+    fn with_frame_time(us: u64) -> TrailerLight<MockLeds, NoDelay, StepClock> {
+        let clock = StepClock {
+            now: Cell::new(0),
+            step: us,
+        };
+        TrailerLight::new(MockLeds::default(), NoDelay, clock)
+    }
+
+    fn trailer_light() -> TrailerLight<MockLeds, NoDelay, StepClock> {
+        with_frame_time(3_000)
+    }
+
+    #[test]
+    fn run_writes_until_the_effect_has_finished() {
+        let mut tl = trailer_light();
+        tl.run(&mut Blink);
+
+        // 2 s at 3 ms per frame
+        let frames = &tl.led.frames;
+        assert_eq!(frames.len(), 667);
+        let lit: Vec<usize> = (0..NUM_LEDS).filter(|&i| frames[0][i].r > 0).collect();
+        assert_eq!(lit, [27, 28, 29, 30]);
+        assert!(frames
+            .last()
+            .unwrap()
+            .iter()
+            .all(|c| *c == Color::new(0, 0, 0)));
+    }
+
+    #[test]
+    fn turn_on_animation_is_symmetric() {
+        let mut tl = trailer_light();
+        tl.run(&mut TurnOn::new());
+
+        for frame in &tl.led.frames {
+            assert_eq!(frame.len(), NUM_LEDS);
+            for i in 0..NUM_LEDS / 2 {
+                assert_eq!(frame[i], frame[NUM_LEDS - i - 1]);
+            }
+        }
+        let last = tl.led.frames.last().unwrap();
+        assert!(last.iter().all(|c| *c == Color::new(VAL_3 as u8, 0, 0)));
+    }
+
+    #[test]
+    fn speed_doesnt_depend_on_frame_time() {
+        let mut fast = with_frame_time(3_000);
+        fast.run(&mut Wave::new());
+        let mut slow = with_frame_time(9_000);
+        slow.run(&mut Wave::new());
+
+        // a third of the frames, both take the same time within a slow frame
+        let frames = fast.led.frames.len();
+        assert!(frames.abs_diff(3 * slow.led.frames.len()) <= 3);
+        assert!(fast.now().as_micros().abs_diff(slow.now().as_micros()) <= 2 * 9_000);
+    }
+
+    #[test]
+    fn crossfade_to_the_wave_doesnt_dip() {
+        let (mut turn_on, mut wave) = (TurnOn::new(), Wave::new());
+        let mut tl = trailer_light();
+        let mut runner = Runner::<&mut dyn Effect>::new();
+        runner.start(&mut turn_on, tl.now());
+        tl.play(&mut runner);
+        let faded_from = tl.led.frames.len();
+
+        let transition = Transition::new(Duration::from_millis(500), Easing::EaseInOut);
+        runner.crossfade(&mut wave, tl.now(), transition);
+        tl.play(&mut runner);
+
+        // both effects are at least at VAL_3 everywhere
+        let frames = &tl.led.frames[faded_from..];
+        assert!(frames.iter().flatten().all(|c| c.r >= VAL_3 as u8));
+    }
+
+    #[test]
+    fn brake_flashes_on_top_of_the_wave() {
+        let (mut wave, mut brake) = (Wave::new(), EmergencyBrake);
+        let mut tl = trailer_light();
+        let mut compositor = Compositor::<2>::new();
+        compositor.add(&mut wave, tl.now(), Layer::new(BlendMode::Max));
+        let brake = compositor
+            .add(
+                &mut brake,
+                tl.now(),
+                Layer::new(BlendMode::Alpha).priority(1),
+            )
+            .unwrap();
+
+        while compositor.contains(brake) {
+            tl.step(&mut compositor);
+        }
+        // the wave is still running once the brake has finished
+        assert!(tl.step(&mut compositor));
+        let frames = &tl.led.frames;
+        assert!(frames[0].iter().all(|c| c.r > VAL_3 as u8));
+        assert!(frames.last().unwrap()[0].r == VAL_3 as u8);
+    }
+
+    #[test]
+    fn full_white_is_dimmed() {
+        let mut tl = trailer_light();
+        tl.color(Color::new(255, 255, 255));
+
+        let frame = &tl.led.frames[0];
+        assert!(PowerModel::default().within_budget(frame));
+        assert!(frame.iter().all(|c| c.r == c.g && c.g == c.b && c.r > 0));
+        assert_eq!(tl.throttle_stats().throttled_frames, 1);
+    }
+
+    #[test]
+    fn brake_light_is_dimmed_to_the_budget() {
+        let mut tl = trailer_light();
+        // red LEDs drawing twice as much as usual push full red over budget
+        tl.set_power_model(PowerModel {
+            red_ua: 40_000,
+            ..PowerModel::default()
+        });
+        let mut runner = Runner::new();
+        runner.start(Wave::new(), tl.now());
+        let mut brake = BrakeLight::new(runner);
+        tl.step(&mut brake);
+        brake.brake(true, tl.now());
+        tl.step(&mut brake);
+
+        let frame = tl.led.frames.last().unwrap();
+        assert!(frame.iter().all(|c| c.r > VAL_3 as u8 && c.r < 255));
+        // dimmed evenly, up to the dithering
+        assert!(frame
+            .iter()
+            .all(|c| c.r.abs_diff(frame[0].r) <= 1 && c.g == 0));
+        assert!(PowerModel::default().within_budget(frame));
+        assert!(tl.throttle_stats().last_scale < FULL_SCALE);
+    }
+
+    #[test]
+    fn gamma_is_applied_before_writing() {
+        let mut tl = trailer_light();
+        tl.set_gamma(&gamma::CIE_LIGHTNESS);
+        for _ in 0..5 {
+            tl.color(Color::new(128, 0, 255));
+        }
+        // 47.39 after correction, dithered over the frames
+        let frames = &tl.led.frames;
+        assert!(frames.iter().flatten().all(|c| c.g == 0 && c.b == 255));
+        assert_eq!(frames.iter().map(|f| f[0].r as u32).sum::<u32>(), 236);
+    }
+}
@@ -0,0 +1,110 @@
+//! Crossfading from one effect to the next.
+//!
+//! A crossfade mixes every channel linearly between the two frames, so the
+//! result always lies between them: no frame of a transition is ever darker
+//! than the darker of the two effects, which would make the light flicker.
+
+use core::time::Duration;
+
+use crate::{easing::Easing, effect::Frame, Color16};
+
+/// Transition used when the light changes modes.
+pub const MODE_CHANGE: Transition = Transition::new(Duration::from_millis(1000), Easing::EaseInOut);
+
+/// Weight of the frame faded to once the transition is complete.
+pub const FULL_WEIGHT: u16 = u16::MAX;
+
+/// How to get from one effect to the next, see
+/// [`Runner::crossfade`](crate::effect::Runner::crossfade).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Transition {
+    pub duration: Duration,
+    pub easing: Easing,
+}
+
+impl Transition {
+    pub const fn new(duration: Duration, easing: Easing) -> Self {
+        Transition { duration, easing }
+    }
+
+    /// Weight of the new effect `t` after the start of the transition,
+    /// [`FULL_WEIGHT`] once it's done.
+    pub fn weight(&self, t: Duration) -> u16 {
+        if t >= self.duration {
+            return FULL_WEIGHT;
+        }
+        let x = t.as_micros() as f32 / self.duration.as_micros() as f32;
+        (self.easing.apply(x) * FULL_WEIGHT as f32) as u16
+    }
+}
+
+/// Mixes `from` into `to`, `weight` is the share of `to`.
+pub fn crossfade(from: &Frame, to: &mut Frame, weight: u16) {
+    let channel = |from: u16, to: u16| {
+        let from = from as i64;
+        // truncating towards `from` keeps the result between both values
+        (from + (to as i64 - from) * weight as i64 / FULL_WEIGHT as i64) as u16
+    };
+    for (from, to) in from.iter().zip(to.iter_mut()) {
+        *to = Color16::new(
+            channel(from.r, to.r),
+            channel(from.g, to.g),
+            channel(from.b, to.b),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NUM_LEDS;
+
+    #[test]
+    fn weight_follows_the_easing() {
+        let linear = Transition::new(Duration::from_millis(100), Easing::Linear);
+        assert_eq!(linear.weight(Duration::ZERO), 0);
+        assert_eq!(linear.weight(Duration::from_millis(50)), FULL_WEIGHT / 2);
+        assert_eq!(linear.weight(Duration::from_millis(100)), FULL_WEIGHT);
+        assert_eq!(linear.weight(Duration::from_secs(1)), FULL_WEIGHT);
+
+        let ease_in = Transition::new(Duration::from_millis(100), Easing::EaseIn);
+        assert!(ease_in.weight(Duration::from_millis(50)) < FULL_WEIGHT / 2);
+
+        let instant = Transition::new(Duration::ZERO, Easing::Linear);
+        assert_eq!(instant.weight(Duration::ZERO), FULL_WEIGHT);
+    }
+
+    #[test]
+    fn never_darker_than_either_frame() {
+        let mut from = [Color16::default(); NUM_LEDS];
+        let mut to = [Color16::default(); NUM_LEDS];
+        for i in 0..NUM_LEDS {
+            let v = (i as u32 * 40_009 % 65_536) as u16;
+            from[i] = Color16::new(v, u16::MAX - v, 1);
+            to[i] = Color16::new(u16::MAX - v, v, 0);
+        }
+        for weight in (0..=FULL_WEIGHT).step_by(257) {
+            let mut mixed = to;
+            crossfade(&from, &mut mixed, weight);
+            for ((a, b), m) in from.iter().zip(&to).zip(&mixed) {
+                for (a, b, m) in [(a.r, b.r, m.r), (a.g, b.g, m.g), (a.b, b.b, m.b)] {
+                    assert!(a.min(b) <= m && m <= a.max(b));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn ends_at_the_frames() {
+        let from = [Color16::new(1000, 0, 500); NUM_LEDS];
+        let to = [Color16::new(0, 2000, 500); NUM_LEDS];
+
+        let mut mixed = to;
+        crossfade(&from, &mut mixed, 0);
+        assert_eq!(mixed, from);
+
+        let mut mixed = to;
+        crossfade(&from, &mut mixed, FULL_WEIGHT);
+        assert_eq!(mixed, to);
+    }
+}
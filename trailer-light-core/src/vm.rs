@@ -0,0 +1,444 @@
+//! Bytecode interpreter for effects that aren't compiled into the firmware.
+//!
+//! A program is a byte slice of instructions, each an opcode followed by its
+//! arguments (multi-byte ones little endian):
+//!
+//! | opcode     | arguments                                   |
+//! |------------|---------------------------------------------|
+//! | [`END`]    |                                             |
+//! | [`COLOR`]  | `r: u8, g: u8, b: u8`                       |
+//! | [`FILL`]   | `first: u8, count: u8`                      |
+//! | [`SWEEP`]  | `first: u8, count: u8, start: i8, end: i8, speed: u16, bb: u8, tb: u8, hb: u8, hw: u8` |
+//! | [`MIRROR`] | `on: u8`                                    |
+//! | [`WAIT`]   | `ms: u16`                                   |
+//! | [`LOOP`]   | `times: u8`                                 |
+//! | [`NEXT`]   |                                             |
+//!
+//! `FILL` sets LEDs to the current color. `SWEEP` plays an
+//! [`AnimationContext`] on `count` LEDs starting at `first`, in the current
+//! color, and continues once the highlight has reached `end`. `speed` is in
+//! 1/100 LEDs per second, `hw` in 1/10 LEDs. With `MIRROR` on, the right half
+//! of the strip is mirrored onto the left one. `LOOP` repeats everything up
+//! to its `NEXT`, `times` 0 repeats forever.
+//!
+//! Time only passes in `WAIT` and `SWEEP`, everything else happens at once.
+//! To keep a broken program from locking up the light, it may only execute a
+//! limited number of instructions without time passing. A program exceeding
+//! it, or running into any other error, is stopped with a [`Fault`]. Catching
+//! up on a late frame doesn't count against it, as long as time passes.
+//!
+//! The host crate has an assembler for a text format of these instructions.
+
+use core::time::Duration;
+
+use crate::{
+    color::scale,
+    effect::{Effect, Frame},
+    fixed::in_range,
+    layout::View,
+    Color, Color16, NUM_LEDS,
+};
+
+#[cfg(feature = "fixed-point")]
+use crate::fixed::FixedAnimationContext as AnimationContext;
+#[cfg(not(feature = "fixed-point"))]
+use crate::AnimationContext;
+
+pub const END: u8 = 0x00;
+pub const COLOR: u8 = 0x01;
+pub const FILL: u8 = 0x02;
+pub const SWEEP: u8 = 0x03;
+pub const MIRROR: u8 = 0x04;
+pub const WAIT: u8 = 0x05;
+pub const LOOP: u8 = 0x06;
+pub const NEXT: u8 = 0x07;
+
+/// Length of the arguments of `opcode`, `None` for invalid opcodes.
+pub const fn args_len(opcode: u8) -> Option<usize> {
+    Some(match opcode {
+        END | NEXT => 0,
+        MIRROR | LOOP => 1,
+        FILL | WAIT => 2,
+        COLOR => 3,
+        SWEEP => 10,
+        _ => return None,
+    })
+}
+
+/// Instructions a program may execute without time passing, by default.
+pub const DEFAULT_BUDGET: u16 = 256;
+
+/// Maximum nesting depth of loops.
+pub const MAX_LOOP_DEPTH: usize = 4;
+
+/// Why a program was stopped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// The instruction budget was used up without time passing.
+    BudgetExceeded,
+    InvalidOpcode {
+        pc: usize,
+        opcode: u8,
+    },
+    /// The program ends in the middle of an instruction.
+    Truncated {
+        pc: usize,
+    },
+    /// LEDs outside of the strip, or sweep parameters `AnimationContext`
+    /// doesn't take, see also [`in_range`].
+    InvalidArgument {
+        pc: usize,
+    },
+    LoopTooDeep {
+        pc: usize,
+    },
+    NextWithoutLoop {
+        pc: usize,
+    },
+}
+
+struct Loop {
+    start: usize,
+    // 0 repeats forever
+    remaining: u8,
+}
+
+struct Sweep {
+    ctx: AnimationContext,
+    first: usize,
+    count: usize,
+}
+
+/// Runs a program as an [`Effect`].
+pub struct Vm<'a> {
+    code: &'a [u8],
+    pc: usize,
+    budget: u16,
+    color: Color,
+    mirror: bool,
+    frame: Frame,
+    loops: [Option<Loop>; MAX_LOOP_DEPTH],
+    depth: usize,
+    sweep: Option<Sweep>,
+    // time at which the current instruction started, relative to the start
+    // of the program
+    time: Duration,
+    done: bool,
+    fault: Option<Fault>,
+}
+
+impl<'a> Vm<'a> {
+    pub fn new(code: &'a [u8]) -> Self {
+        Vm {
+            code,
+            pc: 0,
+            budget: DEFAULT_BUDGET,
+            color: Color::new(255, 0, 0),
+            mirror: false,
+            frame: [Color16::new(0, 0, 0); NUM_LEDS],
+            loops: [(); MAX_LOOP_DEPTH].map(|_| None),
+            depth: 0,
+            sweep: None,
+            time: Duration::ZERO,
+            done: false,
+            fault: None,
+        }
+    }
+
+    /// Sets how many instructions may be executed without time passing.
+    pub fn set_budget(&mut self, budget: u16) {
+        self.budget = budget;
+    }
+
+    /// Why the program was stopped, if it was.
+    pub fn fault(&self) -> Option<Fault> {
+        self.fault
+    }
+
+    /// Executes instructions until the program waits for time to pass at
+    /// `t`, ends or faults.
+    fn run(&mut self, t: Duration) -> Result<(), Fault> {
+        let mut budget = self.budget;
+        loop {
+            if let Some(sweep) = &self.sweep {
+                let mut v = [0; NUM_LEDS];
+                let running = sweep
+                    .ctx
+                    .render(t.saturating_sub(self.time), &mut v[..sweep.count]);
+                for (c, &v) in self.frame[sweep.first..].iter_mut().zip(&v[..sweep.count]) {
+                    *c = scale(self.color, v);
+                }
+                if running {
+                    return Ok(());
+                }
+                let duration = sweep.ctx.duration();
+                if !duration.is_zero() {
+                    budget = self.budget;
+                }
+                self.time += duration;
+                self.sweep = None;
+            }
+            if self.time > t || self.done {
+                return Ok(());
+            }
+            if budget == 0 {
+                return Err(Fault::BudgetExceeded);
+            }
+            budget -= 1;
+            let time = self.time;
+            self.step()?;
+            if self.time != time {
+                budget = self.budget;
+            }
+        }
+    }
+
+    /// Executes the instruction at `pc`.
+    fn step(&mut self) -> Result<(), Fault> {
+        let pc = self.pc;
+        let Some(&opcode) = self.code.get(pc) else {
+            // running off the end is the same as `END`
+            self.done = true;
+            return Ok(());
+        };
+        let len = args_len(opcode).ok_or(Fault::InvalidOpcode { pc, opcode })?;
+        let args = self
+            .code
+            .get(pc + 1..pc + 1 + len)
+            .ok_or(Fault::Truncated { pc })?;
+        self.pc += 1 + len;
+        let invalid = Fault::InvalidArgument { pc };
+
+        match opcode {
+            END => self.done = true,
+            COLOR => self.color = Color::new(args[0], args[1], args[2]),
+            FILL => {
+                let leds = leds(args[0], args[1]).ok_or(invalid)?;
+                self.frame[leds].fill(scale(self.color, u16::MAX));
+            }
+            SWEEP => {
+                let leds = leds(args[0], args[1]).ok_or(invalid)?;
+                let [start, end] = [args[2] as i8 as f32, args[3] as i8 as f32];
+                let speed = u16::from_le_bytes([args[4], args[5]]) as f32 / 100.0;
+                let [bb, tb, hb] = [args[6] as f32, args[7] as f32, args[8] as f32];
+                let hw = args[9] as f32 / 10.0;
+                // `AnimationContext::new` asserts these, and the fixed-point
+                // one that positions are in range
+                if !(bb <= tb && tb <= hb && speed > 0.0 && in_range(start) && in_range(end)) {
+                    return Err(invalid);
+                }
+                self.sweep = Some(Sweep {
+                    ctx: AnimationContext::new(start, end, speed, bb, tb, hb, hw),
+                    first: leds.start,
+                    count: leds.len(),
+                });
+            }
+            MIRROR => self.mirror = args[0] != 0,
+            WAIT => {
+                self.time += Duration::from_millis(u16::from_le_bytes([args[0], args[1]]).into())
+            }
+            LOOP => {
+                let slot = self
+                    .loops
+                    .get_mut(self.depth)
+                    .ok_or(Fault::LoopTooDeep { pc })?;
+                *slot = Some(Loop {
+                    start: self.pc,
+                    remaining: args[0],
+                });
+                self.depth += 1;
+            }
+            NEXT => {
+                let top = self
+                    .depth
+                    .checked_sub(1)
+                    .ok_or(Fault::NextWithoutLoop { pc })?;
+                let l = self.loops[top].as_mut().unwrap();
+                match l.remaining {
+                    0 => self.pc = l.start,
+                    1 => {
+                        self.loops[top] = None;
+                        self.depth = top;
+                    }
+                    _ => {
+                        l.remaining -= 1;
+                        self.pc = l.start;
+                    }
+                }
+            }
+            _ => unreachable!(),
+        }
+        Ok(())
+    }
+}
+
+/// The LEDs `first..first + count`, if they're on the strip.
+fn leds(first: u8, count: u8) -> Option<core::ops::Range<usize>> {
+    let (first, count) = (first as usize, count as usize);
+    (first + count <= NUM_LEDS).then_some(first..first + count)
+}
+
+impl Effect for Vm<'_> {
+    /// Renders the program at `t`. Returns `false` once it has ended or was
+    /// stopped by a fault, `t` has to increase from call to call.
+    fn render(&mut self, t: Duration, frame: &mut Frame) -> bool {
+        if self.fault.is_none() {
+            if let Err(fault) = self.run(t) {
+                self.fault = Some(fault);
+            }
+        }
+        *frame = self.frame;
+        if self.mirror {
+            let view = View::strip().mirror();
+            for i in 0..view.len() {
+                if let (right, Some(left)) = view.leds(i) {
+                    frame[left] = frame[right];
+                }
+            }
+        }
+        !self.done && self.fault.is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(vm: &mut Vm, ms: u64) -> (bool, Frame) {
+        let mut frame = [Color16::default(); NUM_LEDS];
+        let running = vm.render(Duration::from_millis(ms), &mut frame);
+        (running, frame)
+    }
+
+    const WHITE: Color16 = Color16::new(u16::MAX, u16::MAX, u16::MAX);
+    const BLACK: Color16 = Color16::new(0, 0, 0);
+
+    #[test]
+    fn fill_and_wait() {
+        let code = [
+            COLOR, 255, 255, 255, FILL, 0, 10, WAIT, 100, 0, COLOR, 0, 0, 0, FILL, 0, 5, END,
+        ];
+        let mut vm = Vm::new(&code);
+
+        let (running, frame) = render(&mut vm, 0);
+        assert!(running);
+        assert!(frame[..10].iter().all(|c| *c == WHITE));
+        assert!(frame[10..].iter().all(|c| *c == BLACK));
+
+        let (running, frame) = render(&mut vm, 100);
+        assert!(!running);
+        assert!(frame[..5].iter().all(|c| *c == BLACK));
+        assert!(frame[5..10].iter().all(|c| *c == WHITE));
+        assert_eq!(vm.fault(), None);
+    }
+
+    #[test]
+    fn loops_repeat() {
+        // blinks LED 0 three times, 10 ms on and 10 ms off
+        let code = [
+            LOOP, 3, COLOR, 255, 0, 0, FILL, 0, 1, WAIT, 10, 0, COLOR, 0, 0, 0, FILL, 0, 1, WAIT,
+            10, 0, NEXT, END,
+        ];
+        let mut vm = Vm::new(&code);
+        let mut on = 0;
+        let mut ms = 0;
+        while let (true, frame) = render(&mut vm, ms) {
+            on += (frame[0].r > 0) as u32;
+            ms += 1;
+        }
+        assert_eq!((on, ms), (30, 60));
+    }
+
+    #[test]
+    fn sweep_matches_animation_context() {
+        let code = [
+            SWEEP, 29, 29, -3i8 as u8, 32, 0x99, 0x05, 60, 60, 255, 60, END,
+        ];
+        let ctx = AnimationContext::new(-3.0, 32.0, 14.33, 60.0, 60.0, 255.0, 6.0);
+        let mut vm = Vm::new(&code);
+
+        for ms in (0..3000).step_by(7) {
+            let (running, frame) = render(&mut vm, ms);
+            let mut v = [0; 29];
+            let expected = ctx.render(Duration::from_millis(ms), &mut v);
+            assert_eq!(running, expected);
+            for (c, v) in frame[29..].iter().zip(v) {
+                assert_eq!(*c, Color16::new(v, 0, 0));
+            }
+        }
+    }
+
+    #[test]
+    fn mirror_copies_the_right_half() {
+        let code = [MIRROR, 1, FILL, 29, 3, END];
+        let (_, frame) = render(&mut Vm::new(&code), 0);
+        let lit: Vec<usize> = (0..NUM_LEDS).filter(|&i| frame[i].r > 0).collect();
+        assert_eq!(lit, [26, 27, 28, 29, 30, 31]);
+    }
+
+    #[test]
+    fn endless_loop_exceeds_the_budget() {
+        let code = [LOOP, 0, FILL, 0, 1, NEXT];
+        let mut vm = Vm::new(&code);
+        let (running, _) = render(&mut vm, 0);
+        assert!(!running);
+        assert_eq!(vm.fault(), Some(Fault::BudgetExceeded));
+
+        // waiting in the loop is fine
+        let code = [LOOP, 0, WAIT, 1, 0, NEXT];
+        let mut vm = Vm::new(&code);
+        for ms in 0..1000 {
+            assert!(render(&mut vm, ms).0);
+        }
+    }
+
+    #[test]
+    fn late_frames_catch_up_within_the_budget() {
+        // far more instructions than the budget until the next frame
+        let code = [LOOP, 0, FILL, 0, 1, WAIT, 1, 0, NEXT];
+        let mut vm = Vm::new(&code);
+        assert!(render(&mut vm, 0).0);
+        assert!(render(&mut vm, 1_000).0);
+        assert_eq!(vm.fault(), None);
+
+        // waiting no time at all doesn't help
+        let code = [LOOP, 0, WAIT, 0, 0, NEXT];
+        let mut vm = Vm::new(&code);
+        assert!(!render(&mut vm, 0).0);
+        assert_eq!(vm.fault(), Some(Fault::BudgetExceeded));
+    }
+
+    #[test]
+    fn faults() {
+        let fault = |code: &[u8]| {
+            let mut vm = Vm::new(code);
+            render(&mut vm, 0);
+            vm.fault()
+        };
+        assert_eq!(
+            fault(&[0xff]),
+            Some(Fault::InvalidOpcode {
+                pc: 0,
+                opcode: 0xff
+            })
+        );
+        assert_eq!(
+            fault(&[WAIT, 0, 0, COLOR, 1]),
+            Some(Fault::Truncated { pc: 3 })
+        );
+        assert_eq!(
+            fault(&[FILL, 50, 10]),
+            Some(Fault::InvalidArgument { pc: 0 })
+        );
+        // base brightness above target brightness
+        let sweep = [SWEEP, 0, 10, 0, 10, 100, 0, 50, 10, 255, 10];
+        assert_eq!(fault(&sweep), Some(Fault::InvalidArgument { pc: 0 }));
+        // starting too far from the end of the strip
+        let sweep = [SWEEP, 0, 10, -100_i8 as u8, 10, 100, 0, 10, 50, 255, 10];
+        assert_eq!(fault(&sweep), Some(Fault::InvalidArgument { pc: 0 }));
+        assert_eq!(fault(&[NEXT]), Some(Fault::NextWithoutLoop { pc: 0 }));
+        let nested = [LOOP, 1, LOOP, 1, LOOP, 1, LOOP, 1, LOOP, 1];
+        assert_eq!(fault(&nested), Some(Fault::LoopTooDeep { pc: 8 }));
+        assert_eq!(fault(&[]), None);
+    }
+}
@@ -0,0 +1,282 @@
+//! Riding speed from a reed switch or hall sensor on a trailer wheel.
+//!
+//! The sensor closes once per revolution. The firmware calls
+//! [`WheelSensor::pulse`] with the time of every edge, usually from the GPIO
+//! interrupt, and the main loop reads the speed and deceleration from it.
+//! Nothing in here touches the hardware, so it runs on the host as well.
+//!
+//! Speeds are in mm/s and decelerations in mm/s², positive while slowing
+//! down. [`Hysteresis`](crate::braking::Hysteresis) turns the deceleration in
+//! milli-g into brake events, [`tempo`] the speed into a tempo for the
+//! animations.
+
+use core::time::Duration;
+
+use crate::{effect::NORMAL_TEMPO, time::Instant};
+
+/// 16" trailer wheel.
+pub const CIRCUMFERENCE_MM: u32 = 1_280;
+
+/// Edges closer than this to the last pulse are bounces of the switch. Fast
+/// enough for 200 km/h on the default wheel.
+pub const DEBOUNCE: Duration = Duration::from_millis(20);
+
+/// Without a pulse for this long, the wheel counts as standing still.
+pub const STOP_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Standard gravity, in mm/s².
+const G: i64 = 9_807;
+
+/// Counts the revolutions of a wheel.
+#[derive(Clone, Debug)]
+pub struct WheelSensor {
+    circumference_mm: u32,
+    debounce: Duration,
+    timeout: Duration,
+    last: Option<Instant>,
+    // time of the last revolution, `None` while standing still
+    interval: Option<Duration>,
+    deceleration: i32,
+}
+
+impl WheelSensor {
+    pub const fn new() -> Self {
+        WheelSensor {
+            circumference_mm: CIRCUMFERENCE_MM,
+            debounce: DEBOUNCE,
+            timeout: STOP_TIMEOUT,
+            last: None,
+            interval: None,
+            deceleration: 0,
+        }
+    }
+
+    /// Circumference of the wheel in mm, [`CIRCUMFERENCE_MM`] by default.
+    pub const fn circumference(mut self, mm: u32) -> Self {
+        self.circumference_mm = mm;
+        self
+    }
+
+    /// Minimum time between two pulses, [`DEBOUNCE`] by default.
+    pub const fn debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    /// Time without pulses after which the wheel stands still,
+    /// [`STOP_TIMEOUT`] by default.
+    pub const fn stop_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Registers an edge of the sensor at `now`. Returns `false` if it was
+    /// ignored as a bounce.
+    pub fn pulse(&mut self, now: Instant) -> bool {
+        let Some(last) = self.last else {
+            self.last = Some(now);
+            return true;
+        };
+        let interval = now - last;
+        if interval < self.debounce {
+            return false;
+        }
+        self.last = Some(now);
+        if interval >= self.timeout {
+            // starting off, the speed isn't known before the next pulse
+            self.interval = None;
+            self.deceleration = 0;
+            return true;
+        }
+        if let Some(previous) = self.interval {
+            // the speeds are averages over the intervals, so they're half
+            // of both intervals apart
+            let dv = self.speed_over(previous) as i64 - self.speed_over(interval) as i64;
+            let dt = (previous + interval).as_micros() as i64 / 2;
+            self.deceleration = (dv * 1_000_000 / dt) as i32;
+        }
+        self.interval = Some(interval);
+        true
+    }
+
+    /// Speed at `now`, in mm/s.
+    ///
+    /// Until the next pulse it's the speed of the last revolution, unless
+    /// that pulse is already late, then the wheel has slowed down to at most
+    /// the speed that would bring it now.
+    pub fn speed(&self, now: Instant) -> u32 {
+        match self.since_last(now) {
+            Some((interval, elapsed)) => self.speed_over(interval.max(elapsed)),
+            None => 0,
+        }
+    }
+
+    /// Deceleration at `now`, in mm/s².
+    ///
+    /// From the last two revolutions, or larger if the next pulse is late.
+    pub fn deceleration(&self, now: Instant) -> i32 {
+        let Some((interval, elapsed)) = self.since_last(now) else {
+            return 0;
+        };
+        if elapsed <= interval {
+            return self.deceleration;
+        }
+        let dv = self.speed_over(interval) as i64 - self.speed_over(elapsed) as i64;
+        let dt = (interval + elapsed).as_micros() as i64 / 2;
+        self.deceleration.max((dv * 1_000_000 / dt) as i32)
+    }
+
+    /// Like [`deceleration`](Self::deceleration), in milli-g.
+    pub fn deceleration_mg(&self, now: Instant) -> i32 {
+        (self.deceleration(now) as i64 * 1_000 / G) as i32
+    }
+
+    /// The last interval and the time since the last pulse, `None` while
+    /// standing still.
+    fn since_last(&self, now: Instant) -> Option<(Duration, Duration)> {
+        let elapsed = now - self.last?;
+        match self.interval {
+            Some(interval) if elapsed < self.timeout => Some((interval, elapsed)),
+            _ => None,
+        }
+    }
+
+    fn speed_over(&self, interval: Duration) -> u32 {
+        (self.circumference_mm as u64 * 1_000_000 / interval.as_micros().max(1) as u64) as u32
+    }
+}
+
+impl Default for WheelSensor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Speed at which the animations play at [`MAX_TEMPO`], in mm/s (30 km/h).
+pub const MAX_TEMPO_SPEED: u32 = 8_333;
+
+/// Tempo of the animations at full speed, in percent.
+pub const MAX_TEMPO: u32 = 300;
+
+/// Tempo for [`Tempo`](crate::effect::Tempo) at the riding speed `speed`,
+/// normal when standing still and up to [`MAX_TEMPO`] at
+/// [`MAX_TEMPO_SPEED`].
+pub fn tempo(speed: u32) -> u32 {
+    let speed = speed.min(MAX_TEMPO_SPEED);
+    NORMAL_TEMPO + (MAX_TEMPO - NORMAL_TEMPO) * speed / MAX_TEMPO_SPEED
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::braking::{BrakeEvent, Hysteresis};
+
+    fn at_ms(ms: u64) -> Instant {
+        Instant::from_micros(ms * 1_000)
+    }
+
+    /// Pulses of a wheel turning every `intervals` ms, starting at `start`.
+    /// Returns the time of the last one.
+    fn ride(sensor: &mut WheelSensor, start: u64, intervals: &[u64]) -> u64 {
+        let mut t = start;
+        sensor.pulse(at_ms(t));
+        for interval in intervals {
+            t += interval;
+            assert!(sensor.pulse(at_ms(t)));
+        }
+        t
+    }
+
+    #[test]
+    fn speed_from_the_circumference() {
+        let mut sensor = WheelSensor::new().circumference(2_000);
+        assert_eq!(sensor.speed(at_ms(0)), 0);
+        let t = ride(&mut sensor, 0, &[250]);
+        // 2 m in 250 ms
+        assert_eq!(sensor.speed(at_ms(t)), 8_000);
+        assert_eq!(sensor.speed(at_ms(t + 100)), 8_000);
+        // the next pulse is late, so it's slower
+        assert_eq!(sensor.speed(at_ms(t + 500)), 4_000);
+        assert_eq!(sensor.speed(at_ms(t + 3_000)), 0);
+    }
+
+    #[test]
+    fn bounces_are_ignored() {
+        let mut sensor = WheelSensor::new().circumference(2_000);
+        assert!(sensor.pulse(at_ms(0)));
+        assert!(!sensor.pulse(at_ms(2)));
+        assert!(!sensor.pulse(at_ms(19)));
+        assert!(sensor.pulse(at_ms(250)));
+        assert!(!sensor.pulse(at_ms(251)));
+        assert_eq!(sensor.speed(at_ms(260)), 8_000);
+    }
+
+    #[test]
+    fn deceleration_from_successive_intervals() {
+        let mut sensor = WheelSensor::new().circumference(2_000);
+        let t = ride(&mut sensor, 0, &[200, 200]);
+        assert_eq!(sensor.deceleration(at_ms(t)), 0);
+        // 10 m/s to 8 m/s, 225 ms apart
+        let t = ride(&mut sensor, t, &[250]);
+        assert_eq!(sensor.deceleration(at_ms(t)), 8_888);
+        assert_eq!(sensor.deceleration_mg(at_ms(t)), 906);
+        // speeding up again
+        let t = ride(&mut sensor, t, &[200]);
+        assert!(sensor.deceleration(at_ms(t)) < 0);
+        // the next pulse is late
+        assert!(sensor.deceleration(at_ms(t + 400)) > 0);
+        assert_eq!(sensor.deceleration(at_ms(t + 3_000)), 0);
+    }
+
+    #[test]
+    fn starting_off_has_no_speed_yet() {
+        let mut sensor = WheelSensor::new();
+        let t = ride(&mut sensor, 0, &[300, 300]);
+        sensor.pulse(at_ms(t + 10_000));
+        assert_eq!(sensor.speed(at_ms(t + 10_000)), 0);
+        assert_eq!(sensor.deceleration(at_ms(t + 10_000)), 0);
+        sensor.pulse(at_ms(t + 11_000));
+        assert_eq!(sensor.speed(at_ms(t + 11_000)), CIRCUMFERENCE_MM);
+    }
+
+    #[test]
+    fn braking_is_detected() {
+        // 25 km/h, then braking at about 0.3 g down to 10 km/h
+        let mut sensor = WheelSensor::new();
+        let mut hysteresis = Hysteresis::new(150, 70);
+        let mut intervals = [184; 10].to_vec();
+        let mut speed = 6_944.0;
+        while speed > 2_800.0 {
+            let interval = CIRCUMFERENCE_MM as f64 / speed;
+            intervals.push((interval * 1_000.0) as u64);
+            speed -= 2_942.0 * interval;
+        }
+        intervals.extend([460; 10]);
+
+        let mut events = Vec::new();
+        let mut t = 0;
+        sensor.pulse(at_ms(t));
+        for (i, interval) in intervals.iter().enumerate() {
+            t += interval;
+            sensor.pulse(at_ms(t));
+            if let Some(event) = hysteresis.update(sensor.deceleration_mg(at_ms(t))) {
+                events.push((i, event));
+            }
+        }
+        // released once two revolutions take the same time again
+        assert_eq!(
+            events,
+            [
+                (11, BrakeEvent::Applied),
+                (intervals.len() - 9, BrakeEvent::Released)
+            ]
+        );
+    }
+
+    #[test]
+    fn tempo_follows_the_speed() {
+        assert_eq!(tempo(0), NORMAL_TEMPO);
+        assert_eq!(tempo(4_200), 200);
+        assert_eq!(tempo(20_000), MAX_TEMPO);
+    }
+}